@@ -0,0 +1,163 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::Local;
+
+use crate::context::Context;
+use crate::report::TaskStatus;
+
+/// Per-run directory the page state is captured into when a task fails
+#[derive(Clone, Debug)]
+pub struct Artifacts {
+    dir: PathBuf,
+    /// Captures made so far, numbering the file names so a later capture of the same task
+    /// doesn't overwrite an earlier one
+    captures: Arc<AtomicU32>,
+}
+
+impl Artifacts {
+    /// A new directory for this run inside `root`, created on the first capture
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        let run = Local::now().format("run-%Y%m%d-%H%M%S").to_string();
+        Artifacts {
+            dir: root.as_ref().join(run),
+            captures: Arc::default(),
+        }
+    }
+
+    /// Reads `ARTIFACTS_DIR`, defaulting to `artifacts`
+    pub fn from_env() -> Self {
+        Artifacts::new(dotenv::var("ARTIFACTS_DIR").unwrap_or_else(|_| "artifacts".to_string()))
+    }
+
+    /// Path of `name` in the run's directory, creating the directory if needed
+    pub fn file(&self, name: &str) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        Ok(self.dir.join(name))
+    }
+
+    /// Captures the page after an attempt at `task` ended with `status`
+    pub fn capture(&self, ctx: &Context, task: &str, attempt: u32, status: TaskStatus) {
+        self.save(
+            ctx,
+            task,
+            &format!("attempt-{attempt}"),
+            &format!("attempt: {attempt}\nstatus: {status}"),
+        );
+    }
+
+    /// Captures the page after none of the tasks following `task` became ready
+    pub fn capture_branch(&self, ctx: &Context, task: &str, reason: &str) {
+        self.save(ctx, task, "branch", &format!("branch: {reason}"));
+    }
+
+    /// Saves a screenshot, the HTML, the URL and the console messages of the current page.
+    ///
+    /// Each part is saved on its own, so a page that can't take screenshots still gets its HTML
+    /// saved. Does nothing when no browser is open.
+    fn save(&self, ctx: &Context, task: &str, label: &str, details: &str) {
+        let Ok(page) = ctx.page() else {
+            return;
+        };
+        if let Err(e) = fs::create_dir_all(&self.dir) {
+            eprintln!("Failed to create artifacts directory {:?}: {e}", self.dir);
+            return;
+        }
+        let capture = self.captures.fetch_add(1, Ordering::Relaxed) + 1;
+        let base = self
+            .dir
+            .join(format!("{capture:03}-{}-{label}", slug(task)));
+        let results = [
+            (
+                "summary",
+                write(
+                    &base.with_extension("txt"),
+                    format!("task: {task}\n{details}\nurl: {}\n", page.url()),
+                ),
+            ),
+            (
+                "screenshot",
+                page.screenshot()
+                    .and_then(|png| write(&base.with_extension("png"), png)),
+            ),
+            (
+                "html",
+                page.html()
+                    .and_then(|html| write(&base.with_extension("html"), html)),
+            ),
+            (
+                "console",
+                write(
+                    &base.with_extension("console.log"),
+                    page.console_messages().join("\n"),
+                ),
+            ),
+        ];
+        let mut saved = false;
+        for (part, result) in results {
+            match result {
+                Ok(()) => saved = true,
+                Err(e) => eprintln!("Failed to capture {part} for {task:?}: {e}"),
+            }
+        }
+        if saved {
+            println!("Captured page state of {task:?} to {}", base.display());
+        }
+    }
+}
+
+fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Lowercase file name safe version of a task name
+fn slug(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '-',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::mock::{mock_context, MockScript};
+
+    const PLAN_URL: &str = "https://wa.test/Student/Planning/DegreePlans";
+
+    #[test]
+    fn later_captures_keep_earlier_ones() {
+        let root = std::env::temp_dir().join(format!("artifacts-{:016x}", rand::random::<u64>()));
+        let artifacts = Artifacts::new(&root);
+        let terms = vec!["Fall 2025".to_string()];
+        let ctx = mock_context(MockScript::webadvisor(PLAN_URL, &terms), PLAN_URL, true);
+
+        // A restarted run and a forked branch share the same `Artifacts` through clones
+        artifacts.capture(&ctx, "Button WA", 1, TaskStatus::Failed);
+        artifacts
+            .clone()
+            .capture(&ctx, "Button WA", 1, TaskStatus::TimedOut);
+        artifacts.capture_branch(&ctx, "Button WA", "no outgoing task became ready");
+
+        let dir = &artifacts.dir;
+        let summaries: Vec<String> = [
+            "001-button-wa-attempt-1.txt",
+            "002-button-wa-attempt-1.txt",
+            "003-button-wa-branch.txt",
+        ]
+        .iter()
+        .map(|name| fs::read_to_string(dir.join(name)).unwrap())
+        .collect();
+        assert!(summaries[0].contains("status: failed"));
+        assert!(summaries[1].contains("status: timed out"));
+        assert!(summaries[2].contains("branch: no outgoing task became ready"));
+        assert!(dir.join("003-button-wa-branch.html").exists());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
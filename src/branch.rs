@@ -0,0 +1,316 @@
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+use petgraph::prelude::*;
+use tokio::task::JoinSet;
+use tokio::time;
+
+use crate::context::Context;
+use crate::task::{Condition, Guard, Task};
+use crate::TaskEdge;
+
+/// Why an outgoing candidate was not taken
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rejection {
+    GuardFalse(String),
+    GuardFailed(String, String),
+    PreConditionFalse,
+    PreConditionFailed(String),
+    /// The check had not finished when the branch timed out
+    Pending,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::GuardFalse(guard) => write!(f, "guard `{guard}` does not hold"),
+            Rejection::GuardFailed(guard, e) => write!(f, "guard `{guard}` failed: {e}"),
+            Rejection::PreConditionFalse => write!(f, "precondition does not hold"),
+            Rejection::PreConditionFailed(e) => write!(f, "precondition failed: {e}"),
+            Rejection::Pending => write!(f, "precondition did not finish in time"),
+        }
+    }
+}
+
+struct Candidate {
+    node: NodeIndex,
+    priority: i32,
+    guard: Option<Guard>,
+    pre_condition: Condition,
+}
+
+async fn evaluate(
+    guard: Option<Guard>,
+    pre_condition: Condition,
+    ctx: Context,
+) -> Result<(), Rejection> {
+    if let Some(guard) = guard {
+        match (guard.check)(ctx.clone()).await {
+            Ok(true) => {}
+            Ok(false) => return Err(Rejection::GuardFalse(guard.name)),
+            Err(e) => return Err(Rejection::GuardFailed(guard.name, e.to_string())),
+        }
+    }
+    match pre_condition(ctx).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Rejection::PreConditionFalse),
+        Err(e) => Err(Rejection::PreConditionFailed(e.to_string())),
+    }
+}
+
+/// Polls every outgoing candidate of `node` concurrently until one of them is ready.
+///
+/// A candidate is ready once its guard (if any) and its precondition both return `Ok(true)`.
+/// A ready candidate is taken as soon as no candidate with a higher priority is still being
+/// checked, ties go to whichever finished first. Fails with the reason each candidate was
+/// rejected for once the node's branch timeout elapses.
+pub async fn resolve_branch(
+    graph: &DiGraph<Task, TaskEdge>,
+    ctx: &Context,
+    node: NodeIndex,
+) -> Result<NodeIndex> {
+    let policy = &graph[node].policy;
+    let candidates: Vec<Candidate> = graph
+        .edges(node)
+        .filter(|edge| edge.weight().is_outgoing())
+        .map(|edge| Candidate {
+            node: edge.target(),
+            priority: edge.weight().priority,
+            guard: edge.weight().guard.clone(),
+            pre_condition: graph[edge.target()].pre_condition.clone(),
+        })
+        .collect();
+    if candidates.is_empty() {
+        return Err(anyhow!(
+            "Node {:?} has no outgoing edges to follow",
+            graph[node].name
+        ));
+    }
+
+    // Latest known reason per candidate, kept across rounds so a round cut short by the deadline
+    // still reports something useful
+    let mut rejections: Vec<Rejection> = vec![Rejection::Pending; candidates.len()];
+    let deadline = time::Instant::now() + policy.branch_timeout;
+    loop {
+        let mut checks = JoinSet::new();
+        for (i, candidate) in candidates.iter().enumerate() {
+            let future = evaluate(
+                candidate.guard.clone(),
+                candidate.pre_condition.clone(),
+                ctx.clone(),
+            );
+            checks.spawn(async move { (i, future.await) });
+        }
+
+        // Highest priority among the ready candidates, the earliest finisher among equals.
+        // `max_by_key` keeps the last maximum, so search in reverse
+        let best = |ready: &[usize]| {
+            ready
+                .iter()
+                .rev()
+                .max_by_key(|&&i| candidates[i].priority)
+                .copied()
+        };
+        let mut pending: Vec<usize> = (0..candidates.len()).collect();
+        let mut ready: Vec<usize> = Vec::new();
+        let round = async {
+            while let Some(result) = checks.join_next().await {
+                match result {
+                    Ok((i, outcome)) => {
+                        pending.retain(|&p| p != i);
+                        match outcome {
+                            Ok(()) => ready.push(i),
+                            Err(rejection) => rejections[i] = rejection,
+                        }
+                    }
+                    Err(e) => eprintln!("Branch check panicked: {e}"),
+                }
+                // Only keep waiting for candidates which would win over the ready one
+                if let Some(winner) = best(&ready) {
+                    let priority = candidates[winner].priority;
+                    if pending.iter().all(|&p| candidates[p].priority <= priority) {
+                        return;
+                    }
+                }
+            }
+        };
+        let timed_out = time::timeout_at(deadline, round).await.is_err();
+        // Dropping the set aborts the checks which are still running
+        drop(checks);
+
+        if let Some(winner) = best(&ready) {
+            for (i, rejection) in rejections.iter().enumerate() {
+                if i != winner && !ready.contains(&i) {
+                    println!("Rejected {:?}: {rejection}", graph[candidates[i].node].name);
+                }
+            }
+            return Ok(candidates[winner].node);
+        }
+
+        if timed_out || time::Instant::now() + policy.branch_poll >= deadline {
+            let reasons = candidates
+                .iter()
+                .zip(rejections.iter())
+                .map(|(candidate, rejection)| {
+                    format!("{:?}: {rejection}", graph[candidate.node].name)
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(anyhow!(
+                "No outgoing task of {:?} became ready within {:?} ({reasons})",
+                graph[node].name,
+                policy.branch_timeout
+            ));
+        }
+        time::sleep(policy.branch_poll).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::task::TaskPolicy;
+
+    /// Precondition which answers `result` after `delay`
+    fn task(name: &str, delay: u64, result: Result<bool, &'static str>) -> Task {
+        Task::new_async(
+            name,
+            |_| async { Ok(()) },
+            move |_| async move {
+                time::sleep(Duration::from_millis(delay)).await;
+                result.map_err(|e| anyhow!(e))
+            },
+            false,
+        )
+    }
+
+    /// Graph of a start node followed by the given candidates with their priority and guard
+    fn branch(
+        branch_timeout: u64,
+        candidates: Vec<(Task, i32, Option<Guard>)>,
+    ) -> (DiGraph<Task, TaskEdge>, NodeIndex) {
+        let mut graph = DiGraph::new();
+        let start = graph.add_node(task("Start", 0, Ok(true)).with_policy(TaskPolicy {
+            branch_timeout: Duration::from_millis(branch_timeout),
+            ..TaskPolicy::default()
+        }));
+        for (candidate, priority, guard) in candidates {
+            let node = graph.add_node(candidate);
+            graph.add_edge(start, node, TaskEdge::outgoing(priority, guard));
+        }
+        (graph, start)
+    }
+
+    async fn resolve(graph: &DiGraph<Task, TaskEdge>, start: NodeIndex) -> Result<String> {
+        let node = resolve_branch(graph, &Context::default(), start).await?;
+        Ok(graph[node].name.clone())
+    }
+
+    #[tokio::test]
+    async fn first_ready_candidate_wins() {
+        let (graph, start) = branch(
+            5000,
+            vec![
+                (task("Hanging", 60_000, Ok(true)), 0, None),
+                (task("Slow", 200, Ok(true)), 0, None),
+                (task("Fast", 0, Ok(true)), 0, None),
+            ],
+        );
+        let started = time::Instant::now();
+        assert_eq!(resolve(&graph, start).await.unwrap(), "Fast");
+        assert!(started.elapsed() < Duration::from_millis(150));
+    }
+
+    #[tokio::test]
+    async fn priority_breaks_ties() {
+        // The ready candidate waits for the slower one with a higher priority
+        let (graph, start) = branch(
+            5000,
+            vec![
+                (task("Low", 0, Ok(true)), 0, None),
+                (task("High", 100, Ok(true)), 1, None),
+            ],
+        );
+        assert_eq!(resolve(&graph, start).await.unwrap(), "High");
+
+        // But not for one with a lower priority
+        let (graph, start) = branch(
+            5000,
+            vec![
+                (task("Low", 60_000, Ok(true)), 0, None),
+                (task("High", 0, Ok(true)), 1, None),
+            ],
+        );
+        assert_eq!(resolve(&graph, start).await.unwrap(), "High");
+
+        // And takes over when the one with a higher priority turns out not to be ready
+        let (graph, start) = branch(
+            5000,
+            vec![
+                (task("Low", 0, Ok(true)), 0, None),
+                (task("High", 100, Ok(false)), 1, None),
+            ],
+        );
+        assert_eq!(resolve(&graph, start).await.unwrap(), "Low");
+
+        // Or when it is still running at the deadline
+        let (graph, start) = branch(
+            200,
+            vec![
+                (task("Low", 0, Ok(true)), 0, None),
+                (task("High", 60_000, Ok(true)), 1, None),
+            ],
+        );
+        assert_eq!(resolve(&graph, start).await.unwrap(), "Low");
+    }
+
+    #[tokio::test]
+    async fn rejections_keep_their_reasons() {
+        let closed = Guard::new_async("test.closed", |_| async { Ok(false) });
+        let broken = Guard::new_async("test.broken", |_| async { Err(anyhow!("no page")) });
+        let (graph, start) = branch(
+            100,
+            vec![
+                (task("False", 0, Ok(false)), 0, None),
+                (task("Failing", 0, Err("boom")), 0, None),
+                (task("Closed", 0, Ok(true)), 0, Some(closed)),
+                (task("Broken", 0, Ok(true)), 0, Some(broken)),
+            ],
+        );
+        let error = resolve(&graph, start).await.unwrap_err().to_string();
+        assert!(
+            error.contains("\"False\": precondition does not hold"),
+            "{error}"
+        );
+        assert!(
+            error.contains("\"Failing\": precondition failed: boom"),
+            "{error}"
+        );
+        assert!(
+            error.contains("\"Closed\": guard `test.closed` does not hold"),
+            "{error}"
+        );
+        assert!(
+            error.contains("\"Broken\": guard `test.broken` failed: no page"),
+            "{error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_the_deadline() {
+        let (graph, start) = branch(100, vec![(task("Hanging", 60_000, Ok(true)), 0, None)]);
+        let started = time::Instant::now();
+        let error = resolve(&graph, start).await.unwrap_err().to_string();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(
+            error.contains("No outgoing task of \"Start\" became ready within 100ms"),
+            "{error}"
+        );
+        assert!(
+            error.contains("\"Hanging\": precondition did not finish in time"),
+            "{error}"
+        );
+    }
+}
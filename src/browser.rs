@@ -1,47 +1,137 @@
-use anyhow::{anyhow, Result};
-use headless_chrome::{Browser, LaunchOptionsBuilder};
+use std::sync::Arc;
 
-use crate::{Context, ContextInner};
+use anyhow::Result;
+
+use crate::context::{Context, ContextInner};
+use crate::cookie_jar::CookieJar;
+use crate::driver::chrome::{ChromeConfig, ChromeDriver};
+use crate::driver::Driver;
+use crate::har::HarRecorder;
+use crate::task::{Task, TaskRegistry};
+
+pub const PLAN_PATH: &str = "/Student/Planning/DegreePlans";
+
+/// `WA_BASE_URL` without a trailing slash, or the university's host
+pub fn webadvisor_base_url() -> String {
+    let base = dotenv::var("WA_BASE_URL")
+        .unwrap_or_else(|_| "https://colleague-ss.uoguelph.ca".to_string());
+    base.trim_end_matches('/').to_string()
+}
+
+/// Plan page URL, on the `WA_BASE_URL` host if one is configured
+pub fn webadvisor_url() -> String {
+    format!("{}{PLAN_PATH}", webadvisor_base_url())
+}
 
 pub async fn open_browser_precondition(_: Context) -> Result<bool> {
     Ok(true)
 }
 
+/// Opens Chrome with the `ChromeConfig` in the context
 pub async fn open_browser_function(context: Context) -> Result<()> {
-    let launch_options = LaunchOptionsBuilder::default().headless(false).build()?;
-    let mut guard = context
-        .inner
-        .write()
-        .map_err(|_| anyhow!("Poison error"))?;
-    let browser = Browser::new(launch_options)?;
-    let tab = browser.new_tab()?;
-    *guard = Some(ContextInner { browser, tab });
-    Ok(())
+    let config = context.get::<ChromeConfig>()?.unwrap_or_default();
+    let driver: Arc<dyn Driver> = Arc::new(ChromeDriver::launch(&config)?);
+    // When attached to a running browser, take over its WebAdvisor tab if it has one
+    let page = match driver.existing_page(&webadvisor_url())? {
+        Some(page) => {
+            println!("Taking over the open tab at {}", page.url());
+            page
+        }
+        None => driver.new_page()?,
+    };
+    if let Some(recorder) = context.get::<HarRecorder>()? {
+        page.record_network(recorder)?;
+    }
+    context.set_inner(Some(ContextInner { driver, page }))
 }
 
 pub async fn navigate_to_webadvisor_function(context: Context) -> Result<()> {
-    let guard = context
-        .inner
-        .read()
-        .map_err(|_| anyhow!("Poison error"))?;
-    if guard.is_none() {
-        return Err(anyhow!("Expected browser + tab, found `None`."));
-    }
-    let tab = guard.as_ref().unwrap().tab.clone();
-    drop(guard);
-    tab.navigate_to("https://colleague-ss.uoguelph.ca/Student/Planning/DegreePlans")?
-        .wait_until_navigated()?;
-    Ok(())
+    let page = context.page()?;
+    page.navigate_to(&webadvisor_url())
 }
 
 pub async fn navigate_to_webadvisor_precondition(context: Context) -> Result<bool> {
-    let guard = context
-        .inner
-        .read()
-        .map_err(|_| anyhow!("Poison error"))?;
-    if guard.is_none() {
-        Ok(false)
-    } else {
-        Ok(true)
+    context.has_browser()
+}
+
+/// Restores the cookies of a previous session from the `CookieJar` in the context, if any
+pub async fn restore_session_function(context: Context) -> Result<()> {
+    let Some(jar) = context.get::<CookieJar>()? else {
+        println!("No cookie jar configured, not restoring a session");
+        return Ok(());
+    };
+    let cookies = jar.load()?;
+    println!("Restoring {} cookies", cookies.len());
+    if !cookies.is_empty() {
+        context.page()?.set_cookies(cookies)?;
     }
+    Ok(())
+}
+
+/// Saves the cookies of the current session to the `CookieJar` in the context, if any
+pub async fn save_session_function(context: Context) -> Result<()> {
+    let Some(jar) = context.get::<CookieJar>()? else {
+        return Ok(());
+    };
+    let cookies = context.page()?.cookies()?;
+    println!("Saving {} cookies", cookies.len());
+    jar.save(&cookies)
+}
+
+pub async fn navigate_function(context: Context, url: String) -> Result<()> {
+    let page = context.page()?;
+    page.navigate_to(url.as_str())
+}
+
+pub async fn click_function(context: Context, selector: String) -> Result<()> {
+    let page = context.page()?;
+    page.click(selector.as_str())
+}
+
+pub async fn element_precondition(context: Context, selector: String) -> Result<bool> {
+    let page = context.page()?;
+    page.wait_until_navigated()?;
+    page.find_element(selector.as_str()).map(|_| true)
+}
+
+pub fn register(registry: &mut TaskRegistry) {
+    registry.register(
+        "browser.open",
+        open_browser_function,
+        open_browser_precondition,
+    );
+    registry.register(
+        "browser.navigate_wa",
+        navigate_to_webadvisor_function,
+        navigate_to_webadvisor_precondition,
+    );
+    registry.register(
+        "browser.restore_session",
+        restore_session_function,
+        navigate_to_webadvisor_precondition,
+    );
+    registry.register(
+        "browser.save_session",
+        save_session_function,
+        navigate_to_webadvisor_precondition,
+    );
+    registry.register_with("browser.navigate", |name, params, repeatable| {
+        let url = params.require("url")?.to_string();
+        Ok(Task::new_async(
+            name,
+            move |ctx| navigate_function(ctx, url.clone()),
+            navigate_to_webadvisor_precondition,
+            repeatable,
+        ))
+    });
+    registry.register_with("browser.click", |name, params, repeatable| {
+        let selector = params.require("selector")?.to_string();
+        let pre_selector = selector.clone();
+        Ok(Task::new_async(
+            name,
+            move |ctx| click_function(ctx, selector.clone()),
+            move |ctx| element_precondition(ctx, pre_selector.clone()),
+            repeatable,
+        ))
+    });
 }
@@ -0,0 +1,203 @@
+use std::cmp::Reverse;
+
+use petgraph::prelude::*;
+use tokio::time;
+
+use crate::context::Context;
+use crate::task::{Guard, Task};
+use crate::TaskEdge;
+
+/// Tracks how far the graph got so a restart can resume instead of starting over
+#[derive(Clone, Default)]
+pub struct Checkpoint {
+    /// Nodes completed so far in the order they ran, with the guard of the edge each one was
+    /// reached through
+    pub path: Vec<(NodeIndex, Option<Guard>)>,
+}
+
+impl Checkpoint {
+    pub fn record(&mut self, node: NodeIndex, guard: Option<Guard>) {
+        self.path.push((node, guard));
+    }
+
+    pub fn last_completed(&self) -> Option<NodeIndex> {
+        self.path.last().map(|(node, _)| *node)
+    }
+
+    /// Guard of the edge `node` was last reached through
+    pub fn guard_of(&self, node: NodeIndex) -> Option<Guard> {
+        self.path
+            .iter()
+            .rev()
+            .find(|(completed, _)| *completed == node)
+            .and_then(|(_, guard)| guard.clone())
+    }
+}
+
+/// Whether the browser in the context is still alive and responding
+pub fn context_is_healthy(ctx: &Context) -> bool {
+    match ctx.page() {
+        Ok(page) => page.evaluate("true").is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Picks the node to resume from after a failure.
+///
+/// When the browser is still healthy, the successors of the last completed node are tried in the
+/// order the executor would, then the last completed node itself, then the nodes that ran before
+/// it, latest first, each with the guard of the edge it was originally reached through. The first
+/// one whose guard and precondition hold is chosen. Otherwise the browser is dropped and the
+/// graph starts over from `start`.
+pub async fn resume_node(
+    graph: &DiGraph<Task, TaskEdge>,
+    start: NodeIndex,
+    ctx: &Context,
+    checkpoint: &Checkpoint,
+) -> NodeIndex {
+    let Some(last_completed) = checkpoint.last_completed() else {
+        return start;
+    };
+    if !context_is_healthy(ctx) {
+        println!("Browser is no longer healthy, starting over");
+        if let Err(e) = ctx.set_inner(None) {
+            eprintln!("Failed to drop browser: {e}");
+        }
+        return start;
+    }
+
+    let mut successors: Vec<_> = graph
+        .edges(last_completed)
+        .filter(|edge| edge.weight().is_outgoing())
+        .collect();
+    successors.sort_by_key(|edge| Reverse(edge.weight().priority));
+    let mut candidates: Vec<(Option<Guard>, NodeIndex)> = successors
+        .into_iter()
+        .map(|edge| (edge.weight().guard.clone(), edge.target()))
+        .collect();
+    for (node, guard) in checkpoint.path.iter().rev() {
+        if !candidates.iter().any(|(_, candidate)| candidate == node) {
+            candidates.push((guard.clone(), *node));
+        }
+    }
+
+    for (guard, node) in candidates {
+        if node == start {
+            continue;
+        }
+        let task = &graph[node];
+        let ready = async {
+            if let Some(guard) = guard {
+                if !(guard.check)(ctx.clone()).await? {
+                    return Ok(false);
+                }
+            }
+            (task.pre_condition)(ctx.clone()).await
+        };
+        if let Ok(Ok(true)) = time::timeout(task.policy.pre_condition_timeout, ready).await {
+            println!("Resuming from node {:?}", task.name);
+            return node;
+        }
+    }
+    println!("No completed task can be resumed from, starting over");
+    if let Err(e) = ctx.set_inner(None) {
+        eprintln!("Failed to drop browser: {e}");
+    }
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::mock::{mock_context, MockScript};
+
+    const PLAN_URL: &str = "https://wa.test/Student/Planning/DegreePlans";
+
+    /// Task whose precondition holds while the page shows `selector`
+    fn task(name: &str, selector: &'static str) -> Task {
+        Task::new_async(
+            name,
+            |_| async { Ok(()) },
+            move |ctx: Context| async move { Ok(ctx.page()?.find_element(selector).is_ok()) },
+            false,
+        )
+    }
+
+    fn guard(holds: bool) -> Guard {
+        Guard::new_async("test.guard", move |_| async move { Ok(holds) })
+    }
+
+    /// Start -> Email -> Plan, where Email is behind `guard`
+    fn graph(guard: Option<Guard>) -> (DiGraph<Task, TaskEdge>, [NodeIndex; 3]) {
+        let mut graph = DiGraph::new();
+        let start = graph.add_node(task("Start", "body"));
+        let email = graph.add_node(task("Email", "input[type='email']"));
+        let plan = graph.add_node(task("Plan", "#schedule-next-term"));
+        graph.add_edge(start, email, TaskEdge::outgoing(0, guard));
+        graph.add_edge(email, plan, TaskEdge::outgoing(0, None));
+        (graph, [start, email, plan])
+    }
+
+    fn context(signed_in: bool) -> Context {
+        let terms = vec!["Fall 2025".to_string()];
+        mock_context(
+            MockScript::webadvisor(PLAN_URL, &terms),
+            PLAN_URL,
+            signed_in,
+        )
+    }
+
+    #[tokio::test]
+    async fn starts_without_a_checkpoint() {
+        let (graph, [start, ..]) = graph(None);
+        let ctx = context(false);
+        let resume = resume_node(&graph, start, &ctx, &Checkpoint::default()).await;
+        assert_eq!(resume, start);
+        assert!(ctx.has_browser().unwrap());
+    }
+
+    #[tokio::test]
+    async fn starts_over_without_a_browser() {
+        let (graph, [start, email, _]) = graph(None);
+        let mut checkpoint = Checkpoint::default();
+        checkpoint.record(start, None);
+        checkpoint.record(email, None);
+        let resume = resume_node(&graph, start, &Context::default(), &checkpoint).await;
+        assert_eq!(resume, start);
+    }
+
+    #[tokio::test]
+    async fn resumes_at_a_ready_successor() {
+        let (graph, [start, email, plan]) = graph(None);
+        let mut checkpoint = Checkpoint::default();
+        checkpoint.record(start, None);
+        let resume = resume_node(&graph, start, &context(false), &checkpoint).await;
+        assert_eq!(resume, email);
+
+        checkpoint.record(email, None);
+        let resume = resume_node(&graph, start, &context(true), &checkpoint).await;
+        assert_eq!(resume, plan);
+    }
+
+    #[tokio::test]
+    async fn rechecks_the_guard_a_node_was_reached_through() {
+        // Email already ran and the page still shows it, but its guard no longer holds
+        let (graph, [start, email, _]) = graph(Some(guard(false)));
+        let mut checkpoint = Checkpoint::default();
+        checkpoint.record(start, None);
+        checkpoint.record(email, Some(guard(false)));
+        assert_eq!(checkpoint.last_completed(), Some(email));
+        let ctx = context(false);
+        let resume = resume_node(&graph, start, &ctx, &checkpoint).await;
+        assert_eq!(resume, start);
+        assert!(!ctx.has_browser().unwrap());
+
+        let mut checkpoint = Checkpoint::default();
+        checkpoint.record(start, None);
+        checkpoint.record(email, Some(guard(true)));
+        let ctx = context(false);
+        let resume = resume_node(&graph, start, &ctx, &checkpoint).await;
+        assert_eq!(resume, email);
+        assert!(ctx.has_browser().unwrap());
+    }
+}
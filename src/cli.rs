@@ -0,0 +1,72 @@
+use anyhow::{anyhow, Result};
+
+use crate::export::GraphFormat;
+
+/// Command line arguments
+#[derive(Clone, Debug, Default)]
+pub struct Args {
+    /// Print the task graph in the given format instead of running it
+    pub print_graph: Option<GraphFormat>,
+    /// Annotate the printed graph with the outcomes of the last run
+    pub with_last_run: bool,
+    /// Serve local stand-ins of the sites and run the graph against them
+    pub standin: bool,
+    /// Whether to run Chrome headless, overriding `CHROME_HEADLESS` when given
+    pub headless: Option<bool>,
+    /// DevTools WebSocket URL of a running Chrome to attach to, overriding `CHROME_REMOTE_URL`
+    pub attach: Option<String>,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    pub fn parse_from(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--print-graph" => {
+                    let format = args
+                        .next()
+                        .ok_or_else(|| anyhow!("`--print-graph` expects `dot` or `mermaid`"))?;
+                    parsed.print_graph = Some(GraphFormat::parse(&format)?);
+                }
+                "--with-last-run" => parsed.with_last_run = true,
+                "--standin" => parsed.standin = true,
+                "--headless" => parsed.headless = Some(true),
+                "--no-headless" => parsed.headless = Some(false),
+                "--attach" => {
+                    let url = args
+                        .next()
+                        .ok_or_else(|| anyhow!("`--attach` expects a DevTools WebSocket URL"))?;
+                    parsed.attach = Some(url);
+                }
+                _ => return Err(anyhow!("Unknown argument `{arg}`")),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args> {
+        Args::parse_from(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn headless_is_only_overridden_when_given() {
+        assert_eq!(parse(&[]).unwrap().headless, None);
+        assert_eq!(parse(&["--headless"]).unwrap().headless, Some(true));
+        assert_eq!(parse(&["--no-headless"]).unwrap().headless, Some(false));
+        assert_eq!(
+            parse(&["--headless", "--no-headless"]).unwrap().headless,
+            Some(false)
+        );
+        assert!(parse(&["--headed"]).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, TimeDelta, Utc};
+use reqwest::header::DATE;
+
+/// Range the server clock's offset from the local clock lies in
+#[derive(Copy, Clone, Debug)]
+struct Bounds {
+    lower: TimeDelta,
+    upper: TimeDelta,
+}
+
+/// Local clock corrected by the offset of the server's clock, estimated from HTTP `Date` headers.
+///
+/// A `Date` header only has second precision, but every response bounds the offset by when the
+/// request was sent and the response received. Intersecting the bounds of several responses
+/// narrows the offset down below a second.
+#[derive(Clone, Debug, Default)]
+pub struct ServerClock {
+    bounds: Arc<Mutex<Option<Bounds>>>,
+}
+
+impl ServerClock {
+    /// Records a response with the `date` header to a request sent at `sent` and answered at
+    /// `received`, both local times
+    pub fn observe(&self, date: &str, sent: DateTime<Utc>, received: DateTime<Utc>) -> Result<()> {
+        let date = DateTime::parse_from_rfc2822(date)
+            .map_err(|e| anyhow!("Invalid `Date` header {date}: {e}"))?
+            .with_timezone(&Utc);
+        // The server's clock read `date` some time between `sent` and `received`, and shows the
+        // same second for up to a second after
+        let observed = Bounds {
+            lower: date - received,
+            upper: date + TimeDelta::seconds(1) - sent,
+        };
+        let mut bounds = self.bounds.lock().unwrap();
+        *bounds = match *bounds {
+            Some(known) if known.lower.max(observed.lower) <= known.upper.min(observed.upper) => {
+                Some(Bounds {
+                    lower: known.lower.max(observed.lower),
+                    upper: known.upper.min(observed.upper),
+                })
+            }
+            // Disagreeing with earlier responses means one of the clocks was adjusted
+            _ => Some(observed),
+        };
+        Ok(())
+    }
+
+    /// Estimated offset of the server clock, zero until a response was observed
+    pub fn offset(&self) -> TimeDelta {
+        match *self.bounds.lock().unwrap() {
+            Some(bounds) => bounds.lower + (bounds.upper - bounds.lower) / 2,
+            None => TimeDelta::zero(),
+        }
+    }
+
+    /// Current time on the server's clock
+    pub fn now(&self) -> DateTime<Utc> {
+        Utc::now() + self.offset()
+    }
+
+    /// Sends `samples` HEAD requests to `url`, observing the `Date` header of each response
+    pub async fn sample(&self, url: &str, samples: u32) -> Result<()> {
+        // A redirect would read the `Date` of whichever host it leads to, like the sign-in
+        let http = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        for _ in 0..samples {
+            let sent = Utc::now();
+            let response = http.head(url).send().await?;
+            let received = Utc::now();
+            let date = response
+                .headers()
+                .get(DATE)
+                .ok_or_else(|| anyhow!("{url} answered without a `Date` header"))?
+                .to_str()?;
+            self.observe(date, sent, received)?;
+            // Spreads the requests over a second so they see different parts of it
+            tokio::time::sleep(Duration::from_millis(230)).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::standin::{serve, Handler, Request, Response};
+
+    /// Sends every request on to a sign-in host that doesn't resolve
+    struct SignInRedirect;
+
+    impl Handler for SignInRedirect {
+        fn handle(&self, _request: Request) -> Response {
+            Response::redirect("http://sign-in.invalid/login")
+        }
+    }
+
+    #[test]
+    fn narrows_the_offset_down() {
+        let clock = ServerClock::default();
+        assert_eq!(clock.offset(), TimeDelta::zero());
+        let sent = Utc::now();
+        let date = sent + TimeDelta::seconds(10);
+        clock
+            .observe(
+                &date.to_rfc2822(),
+                sent,
+                sent + TimeDelta::milliseconds(200),
+            )
+            .unwrap();
+        let offset = clock.offset();
+        assert!(offset > TimeDelta::seconds(9) && offset < TimeDelta::seconds(11));
+        assert!(clock.observe("not a date", sent, sent).is_err());
+    }
+
+    #[tokio::test]
+    async fn samples_without_following_redirects() {
+        let url = serve(Arc::new(SignInRedirect)).await.unwrap();
+        let clock = ServerClock::default();
+        clock.sample(&url, 2).await.unwrap();
+        assert!(clock.offset().abs() <= TimeDelta::seconds(1));
+    }
+}
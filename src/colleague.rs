@@ -0,0 +1,465 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use reqwest::header::{HeaderMap, HeaderValue, COOKIE};
+use serde::{Deserialize, Serialize};
+
+use crate::browser::PLAN_PATH;
+use crate::driver::Page;
+
+/// Hidden input and header carrying the anti-forgery token
+pub const TOKEN_NAME: &str = "__RequestVerificationToken";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlannedCourse {
+    pub course_id: String,
+    /// `None` when only the course is planned, without picking a section
+    pub section_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlanTerm {
+    /// Term code, e.g. `F25`
+    pub code: String,
+    pub planned_courses: Vec<PlannedCourse>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DegreePlan {
+    pub id: u64,
+    pub person_id: String,
+    pub terms: Vec<PlanTerm>,
+}
+
+impl DegreePlan {
+    /// Sections planned for the term with `code`
+    pub fn sections(&self, code: &str) -> Vec<String> {
+        self.terms
+            .iter()
+            .filter(|term| term.code == code)
+            .flat_map(|term| term.planned_courses.iter())
+            .filter_map(|course| course.section_id.clone())
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SectionRegistration {
+    pub section_id: String,
+    pub action: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RegisterRequest {
+    pub student_id: String,
+    pub section_registrations: Vec<SectionRegistration>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RegistrationMessage {
+    pub section_id: Option<String>,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RegistrationResponse {
+    #[serde(default)]
+    pub messages: Vec<RegistrationMessage>,
+}
+
+impl RegistrationResponse {
+    /// Outcome of each section the response has a message for
+    pub fn outcomes(&self) -> Vec<(String, RegistrationOutcome)> {
+        self.messages
+            .iter()
+            .filter_map(|message| {
+                let section = message.section_id.clone()?;
+                Some((section, RegistrationOutcome::from_message(&message.message)))
+            })
+            .collect()
+    }
+}
+
+/// What registering a section resulted in, read from the message Colleague answered with
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RegistrationOutcome {
+    Registered,
+    Waitlisted,
+    Closed,
+    PrerequisiteMissing,
+    TimeConflict,
+    NotOpen,
+    /// A message that matched none of the other outcomes
+    Unknown,
+}
+
+impl RegistrationOutcome {
+    pub fn from_message(message: &str) -> Self {
+        let message = message.to_lowercase();
+        let words: Vec<&str> = message
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        // Phrases have to appear as whole words, so e.g. "full" doesn't match "successfully"
+        let mentions = |phrases: &[&str]| {
+            phrases.iter().any(|phrase| {
+                let phrase: Vec<&str> = phrase.split(' ').collect();
+                words.windows(phrase.len()).any(|window| window == phrase)
+            })
+        };
+        if mentions(&["not open", "not yet open", "not currently open"]) {
+            RegistrationOutcome::NotOpen
+        } else if mentions(&["prerequisite", "prerequisites", "requisite", "requisites"]) {
+            RegistrationOutcome::PrerequisiteMissing
+        } else if mentions(&["conflict", "conflicts", "conflicting"]) {
+            RegistrationOutcome::TimeConflict
+        } else if mentions(&["waitlist", "waitlisted", "wait list", "wait listed"]) {
+            RegistrationOutcome::Waitlisted
+        } else if mentions(&["closed", "full", "no seats"]) {
+            RegistrationOutcome::Closed
+        } else if mentions(&[
+            "not registered",
+            "not been registered",
+            "not yet registered",
+            "unregistered",
+        ]) {
+            RegistrationOutcome::Unknown
+        } else if mentions(&["registered"]) {
+            RegistrationOutcome::Registered
+        } else {
+            RegistrationOutcome::Unknown
+        }
+    }
+
+    /// Whether retrying can't change the outcome
+    pub fn is_permanent(&self) -> bool {
+        matches!(
+            self,
+            RegistrationOutcome::PrerequisiteMissing | RegistrationOutcome::TimeConflict
+        )
+    }
+}
+
+impl FromStr for RegistrationOutcome {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        Ok(match name.trim() {
+            "registered" => RegistrationOutcome::Registered,
+            "waitlisted" => RegistrationOutcome::Waitlisted,
+            "closed" => RegistrationOutcome::Closed,
+            "prerequisite_missing" => RegistrationOutcome::PrerequisiteMissing,
+            "time_conflict" => RegistrationOutcome::TimeConflict,
+            "not_open" => RegistrationOutcome::NotOpen,
+            _ => return Err(anyhow!("Unknown registration outcome `{name}`")),
+        })
+    }
+}
+
+impl fmt::Display for RegistrationOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RegistrationOutcome::Registered => "registered",
+            RegistrationOutcome::Waitlisted => "waitlisted",
+            RegistrationOutcome::Closed => "closed",
+            RegistrationOutcome::PrerequisiteMissing => "prerequisite missing",
+            RegistrationOutcome::TimeConflict => "time conflict",
+            RegistrationOutcome::NotOpen => "registration not open",
+            RegistrationOutcome::Unknown => "unknown",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// HTTP client for the Colleague Self-Service JSON endpoints behind the plan page, using the
+/// session of the signed in browser
+pub struct ColleagueClient {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl ColleagueClient {
+    /// Client sending `cookies` (as a `Cookie` header value) and the anti-forgery `token`
+    pub fn new(base_url: &str, cookies: &str, token: &str) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        headers.insert(COOKIE, HeaderValue::from_str(cookies)?);
+        headers.insert(TOKEN_NAME, HeaderValue::from_str(token)?);
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(ColleagueClient {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Takes the cookies for `base_url` and the anti-forgery token from a page showing the plan
+    pub fn from_page(page: &Arc<dyn Page>, base_url: &str) -> Result<Self> {
+        let host = reqwest::Url::parse(base_url)?
+            .host_str()
+            .ok_or_else(|| anyhow!("{base_url} has no host"))?
+            .to_string();
+        let cookies = page
+            .cookies()?
+            .into_iter()
+            .filter(|cookie| {
+                let domain = cookie.domain.trim_start_matches('.');
+                host == domain || host.ends_with(&format!(".{domain}"))
+            })
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let token = page
+            .evaluate(&format!(
+                r#"document.querySelector("input[name='{TOKEN_NAME}']")?.value"#
+            ))?
+            .and_then(|token| token.as_str().map(str::to_string))
+            .ok_or_else(|| anyhow!("The plan page has no anti-forgery token"))?;
+        ColleagueClient::new(base_url, &cookies, &token)
+    }
+
+    pub async fn load_plan(&self) -> Result<DegreePlan> {
+        let response = self
+            .http
+            .get(format!("{}{PLAN_PATH}/CurrentAsync", self.base_url))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json().await?)
+    }
+
+    pub async fn register(
+        &self,
+        student_id: &str,
+        sections: &[String],
+    ) -> Result<RegistrationResponse> {
+        let request = RegisterRequest {
+            student_id: student_id.to_string(),
+            section_registrations: sections
+                .iter()
+                .map(|section| SectionRegistration {
+                    section_id: section.clone(),
+                    action: "Add".to_string(),
+                })
+                .collect(),
+        };
+        let response = self
+            .http
+            .post(format!("{}{PLAN_PATH}/RegisterSections", self.base_url))
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::standin::microsoft::{Sessions, SESSION_COOKIE};
+    use crate::standin::serve;
+    use crate::standin::webadvisor::WebAdvisorStandin;
+
+    const CIS: &str = "CIS*2500*0101";
+    const MATH: &str = "MATH*1200*0102";
+
+    fn standin() -> WebAdvisorStandin {
+        WebAdvisorStandin::new(vec!["Fall 2025".to_string()], 0, Duration::ZERO)
+    }
+
+    /// Client for the stand-in at `url`, with the token of its plan page
+    async fn client(url: &str, cookies: &str) -> ColleagueClient {
+        let page = reqwest::Client::new()
+            .get(format!("{url}{PLAN_PATH}"))
+            .header(COOKIE, cookies)
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        let (_, rest) = page
+            .split_once(&format!(r#"name="{TOKEN_NAME}" value=""#))
+            .unwrap();
+        let token = rest.split('"').next().unwrap();
+        ColleagueClient::new(url, cookies, token).unwrap()
+    }
+
+    #[test]
+    fn classifies_messages() {
+        let cases = [
+            (
+                "Registered for CIS*2500*0101",
+                RegistrationOutcome::Registered,
+            ),
+            (
+                "You have registered successfully",
+                RegistrationOutcome::Registered,
+            ),
+            (
+                "Successfully registered for CIS*2500*0101",
+                RegistrationOutcome::Registered,
+            ),
+            (
+                "Already registered for CIS*2500*0101",
+                RegistrationOutcome::Registered,
+            ),
+            (
+                "You are not registered for CIS*2500*0101",
+                RegistrationOutcome::Unknown,
+            ),
+            (
+                "CIS*2500*0101 has not been registered",
+                RegistrationOutcome::Unknown,
+            ),
+            ("Section unregistered", RegistrationOutcome::Unknown),
+            (
+                "Could not register CIS*2500*0101",
+                RegistrationOutcome::Unknown,
+            ),
+            ("CIS*2500*0101 is full", RegistrationOutcome::Closed),
+            (
+                "CIS*2500*0101 is full, you are not registered",
+                RegistrationOutcome::Closed,
+            ),
+            (
+                "No seats remain in CIS*2500*0101",
+                RegistrationOutcome::Closed,
+            ),
+            ("CIS*2500*0101 is closed", RegistrationOutcome::Closed),
+            (
+                "CIS*2500*0101 is full, you were added to the waitlist",
+                RegistrationOutcome::Waitlisted,
+            ),
+            ("You were wait listed", RegistrationOutcome::Waitlisted),
+            (
+                "Pre-requisite not met for CIS*2500*0101",
+                RegistrationOutcome::PrerequisiteMissing,
+            ),
+            (
+                "Prerequisites are missing",
+                RegistrationOutcome::PrerequisiteMissing,
+            ),
+            (
+                "CIS*2500*0101 has a time conflict with another section",
+                RegistrationOutcome::TimeConflict,
+            ),
+            ("Registration is not open yet", RegistrationOutcome::NotOpen),
+            ("Registration is not yet open", RegistrationOutcome::NotOpen),
+            ("", RegistrationOutcome::Unknown),
+        ];
+        for (message, outcome) in cases {
+            assert_eq!(
+                RegistrationOutcome::from_message(message),
+                outcome,
+                "{message}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_outcome_names() {
+        assert_eq!(
+            "waitlisted".parse::<RegistrationOutcome>().unwrap(),
+            RegistrationOutcome::Waitlisted
+        );
+        assert_eq!(
+            " time_conflict ".parse::<RegistrationOutcome>().unwrap(),
+            RegistrationOutcome::TimeConflict
+        );
+        assert!("unknown".parse::<RegistrationOutcome>().is_err());
+        assert!(RegistrationOutcome::TimeConflict.is_permanent());
+        assert!(!RegistrationOutcome::Closed.is_permanent());
+    }
+
+    #[tokio::test]
+    async fn loads_the_plan() {
+        let url = serve(Arc::new(standin())).await.unwrap();
+        let plan = client(&url, "").await.load_plan().await.unwrap();
+        assert_eq!(plan.person_id, "0000001");
+        assert_eq!(
+            plan.sections("F25"),
+            vec![CIS.to_string(), MATH.to_string()]
+        );
+        assert!(plan.sections("W25").is_empty());
+    }
+
+    #[tokio::test]
+    async fn registers_the_sections() {
+        let standin = standin().with_outcome(MATH, RegistrationOutcome::Waitlisted);
+        let url = serve(Arc::new(standin)).await.unwrap();
+        let client = client(&url, "").await;
+        let sections = vec![CIS.to_string(), MATH.to_string()];
+        let response = client.register("0000001", &sections).await.unwrap();
+        assert_eq!(
+            response.outcomes(),
+            vec![
+                (CIS.to_string(), RegistrationOutcome::Registered),
+                (MATH.to_string(), RegistrationOutcome::Waitlisted),
+            ]
+        );
+        // Registering again is answered with "Already registered"
+        let again = client.register("0000001", &sections[..1]).await.unwrap();
+        assert_eq!(
+            again.outcomes(),
+            vec![(CIS.to_string(), RegistrationOutcome::Registered)]
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_back_every_scripted_outcome() {
+        let outcomes = [
+            RegistrationOutcome::Waitlisted,
+            RegistrationOutcome::Closed,
+            RegistrationOutcome::PrerequisiteMissing,
+            RegistrationOutcome::TimeConflict,
+            RegistrationOutcome::NotOpen,
+            RegistrationOutcome::Unknown,
+        ];
+        for outcome in outcomes {
+            let url = serve(Arc::new(standin().with_outcome(CIS, outcome)))
+                .await
+                .unwrap();
+            let response = client(&url, "")
+                .await
+                .register("0000001", &[CIS.to_string()])
+                .await
+                .unwrap();
+            assert_eq!(response.outcomes(), vec![(CIS.to_string(), outcome)]);
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_without_the_token_or_session() {
+        let url = serve(Arc::new(standin())).await.unwrap();
+        let forged = ColleagueClient::new(&url, "", "forged").unwrap();
+        assert!(forged
+            .register("0000001", &[CIS.to_string()])
+            .await
+            .is_err());
+
+        let sessions = Sessions::default();
+        let standin = standin().with_sign_in("http://sign-in.test/login", sessions.clone());
+        let url = serve(Arc::new(standin)).await.unwrap();
+        let signed_out = ColleagueClient::new(&url, "", "token").unwrap();
+        assert!(signed_out.load_plan().await.is_err());
+        let cookies = format!("{SESSION_COOKIE}={}", sessions.issue());
+        let plan = client(&url, &cookies).await.load_plan().await.unwrap();
+        assert_eq!(plan.sections("F25").len(), 2);
+    }
+}
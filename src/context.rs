@@ -0,0 +1,105 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, Result};
+
+use crate::driver::{Driver, Page};
+use crate::har::HarRecorder;
+
+pub struct ContextInner {
+    pub driver: Arc<dyn Driver>,
+    pub page: Arc<dyn Page>,
+}
+
+type State = HashMap<TypeId, Box<dyn Any + Send + Sync>>;
+
+/// Shared between the tasks of a run, holding the browser and a typed store tasks use to hand
+/// data to each other
+#[derive(Clone, Default)]
+pub struct Context {
+    pub inner: Arc<RwLock<Option<ContextInner>>>,
+    state: Arc<RwLock<State>>,
+}
+
+impl Context {
+    /// Current page, failing if no browser has been opened yet
+    pub fn page(&self) -> Result<Arc<dyn Page>> {
+        let guard = self.inner.read().map_err(|_| anyhow!("Poison error"))?;
+        guard
+            .as_ref()
+            .map(|inner| inner.page.clone())
+            .ok_or_else(|| anyhow!("Expected browser + page, found `None`."))
+    }
+
+    pub fn has_browser(&self) -> Result<bool> {
+        let guard = self.inner.read().map_err(|_| anyhow!("Poison error"))?;
+        Ok(guard.is_some())
+    }
+
+    /// Replaces the browser and page, dropping the previous browser
+    pub fn set_inner(&self, inner: Option<ContextInner>) -> Result<()> {
+        let mut guard = self.inner.write().map_err(|_| anyhow!("Poison error"))?;
+        *guard = inner;
+        Ok(())
+    }
+
+    /// Creates a context with a new page of the same browser, opened on the current URL.
+    ///
+    /// The state store is shared with the new context, so forked branches see each other's
+    /// values. Values several branches write to should be merged with [`Context::update`].
+    pub fn fork(&self) -> Result<Context> {
+        let guard = self.inner.read().map_err(|_| anyhow!("Poison error"))?;
+        let inner = guard
+            .as_ref()
+            .ok_or_else(|| anyhow!("Expected browser + page, found `None`."))?;
+        let page = inner.driver.new_page()?;
+        if let Some(recorder) = self.get::<HarRecorder>()? {
+            page.record_network(recorder)?;
+        }
+        let url = inner.page.url();
+        if !url.is_empty() && url != "about:blank" {
+            page.navigate_to(&url)?;
+        }
+        Ok(Context {
+            inner: Arc::new(RwLock::new(Some(ContextInner {
+                driver: inner.driver.clone(),
+                page,
+            }))),
+            state: self.state.clone(),
+        })
+    }
+
+    /// Stores a value, returning the previous value of the same type
+    pub fn insert<T: Any + Send + Sync>(&self, value: T) -> Result<Option<T>> {
+        let mut state = self.state.write().map_err(|_| anyhow!("Poison error"))?;
+        Ok(state
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast::<T>().ok())
+            .map(|previous| *previous))
+    }
+
+    /// Modifies the stored value in place, starting from its default if there is none. The store
+    /// stays locked meanwhile, so concurrent updates don't overwrite each other
+    pub fn update<T, F, R>(&self, f: F) -> Result<R>
+    where
+        T: Any + Send + Sync + Default,
+        F: FnOnce(&mut T) -> R,
+    {
+        let mut state = self.state.write().map_err(|_| anyhow!("Poison error"))?;
+        let value = state
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(T::default()))
+            .downcast_mut::<T>()
+            .ok_or_else(|| anyhow!("Stored value has an unexpected type"))?;
+        Ok(f(value))
+    }
+
+    pub fn get<T: Any + Send + Sync + Clone>(&self) -> Result<Option<T>> {
+        let state = self.state.read().map_err(|_| anyhow!("Poison error"))?;
+        Ok(state
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+            .cloned())
+    }
+}
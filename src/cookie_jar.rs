@@ -0,0 +1,200 @@
+use std::fs;
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::driver::Cookie;
+
+const SALT_LEN: usize = 16;
+const KEY_ITERATIONS: u32 = 100_000;
+
+/// Cookies of a signed in session, stored encrypted so a later run can reuse the session.
+///
+/// The file holds a random salt, a nonce and the AES-256-GCM sealed JSON of the cookies, with the
+/// key derived from a passphrase.
+#[derive(Clone, Debug)]
+pub struct CookieJar {
+    path: PathBuf,
+    passphrase: String,
+}
+
+impl CookieJar {
+    pub fn new(path: impl Into<PathBuf>, passphrase: &str) -> Self {
+        CookieJar {
+            path: path.into(),
+            passphrase: passphrase.to_string(),
+        }
+    }
+
+    /// Reads `COOKIE_JAR` and `COOKIE_JAR_KEY`, `None` when no jar is configured
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(path) = dotenv::var("COOKIE_JAR") else {
+            return Ok(None);
+        };
+        let passphrase = dotenv::var("COOKIE_JAR_KEY")
+            .map_err(|_| anyhow!("`COOKIE_JAR` is set but `COOKIE_JAR_KEY` is missing"))?;
+        Ok(Some(CookieJar::new(path, &passphrase)))
+    }
+
+    fn key(&self, salt: &[u8]) -> Result<LessSafeKey> {
+        let mut key = [0; 32];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            NonZeroU32::new(KEY_ITERATIONS).unwrap(),
+            salt,
+            self.passphrase.as_bytes(),
+            &mut key,
+        );
+        let key = UnboundKey::new(&AES_256_GCM, &key).map_err(|_| anyhow!("Invalid key"))?;
+        Ok(LessSafeKey::new(key))
+    }
+
+    /// Cookies saved by the last run, without the ones that expired since. Empty when nothing
+    /// has been saved yet.
+    pub fn load(&self) -> Result<Vec<Cookie>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let data = fs::read(&self.path)?;
+        if data.len() < SALT_LEN + NONCE_LEN {
+            return Err(anyhow!("Cookie jar {:?} is truncated", self.path));
+        }
+        let (salt, rest) = data.split_at(SALT_LEN);
+        let (nonce, sealed) = rest.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow!("Bad nonce"))?;
+        let mut sealed = sealed.to_vec();
+        let plain = self
+            .key(salt)?
+            .open_in_place(nonce, Aad::empty(), &mut sealed)
+            .map_err(|_| anyhow!("Failed to decrypt cookie jar {:?}, wrong key?", self.path))?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+        let cookies: Vec<Cookie> = serde_json::from_slice(plain)?;
+        Ok(cookies
+            .into_iter()
+            .filter(|cookie| cookie.expires.is_none_or(|expires| expires > now))
+            .collect())
+    }
+
+    pub fn save(&self, cookies: &[Cookie]) -> Result<()> {
+        let random = SystemRandom::new();
+        let mut salt = [0; SALT_LEN];
+        let mut nonce = [0; NONCE_LEN];
+        random
+            .fill(&mut salt)
+            .and_then(|_| random.fill(&mut nonce))
+            .map_err(|_| anyhow!("Failed to generate randomness"))?;
+        let mut sealed = serde_json::to_vec(cookies)?;
+        self.key(&salt)?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut sealed,
+            )
+            .map_err(|_| anyhow!("Failed to encrypt cookie jar"))?;
+        let mut data = salt.to_vec();
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&sealed);
+        fs::write(&self.path, data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cookie(name: &str, expires: Option<f64>) -> Cookie {
+        Cookie {
+            name: name.to_string(),
+            value: "value".to_string(),
+            domain: "login.microsoftonline.com".to_string(),
+            path: "/".to_string(),
+            expires,
+            secure: true,
+            http_only: true,
+        }
+    }
+
+    fn jar_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}-{}.jar", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    /// Whole seconds, so the expiry compares equal after the JSON round trip
+    fn in_an_hour() -> f64 {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        (now.as_secs() + 3600) as f64
+    }
+
+    #[test]
+    fn round_trips_through_the_file() {
+        let path = jar_path("round-trip");
+        let jar = CookieJar::new(&path, "passphrase");
+        assert_eq!(jar.load().unwrap(), vec![]);
+
+        let cookies = vec![
+            cookie("ESTSAUTH", None),
+            cookie("ESTSAUTHPERSISTENT", Some(in_an_hour())),
+        ];
+        jar.save(&cookies).unwrap();
+        assert!(!fs::read(&path)
+            .unwrap()
+            .windows(8)
+            .any(|window| window == b"ESTSAUTH"));
+        assert_eq!(jar.load().unwrap(), cookies);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn drops_expired_cookies() {
+        let path = jar_path("expired");
+        let jar = CookieJar::new(&path, "passphrase");
+        jar.save(&[
+            cookie("expired", Some(1.0)),
+            cookie("valid", Some(in_an_hour())),
+        ])
+        .unwrap();
+        let names: Vec<String> = jar
+            .load()
+            .unwrap()
+            .into_iter()
+            .map(|cookie| cookie.name)
+            .collect();
+        assert_eq!(names, vec!["valid"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_wrong_passphrase() {
+        let path = jar_path("wrong-passphrase");
+        CookieJar::new(&path, "passphrase")
+            .save(&[cookie("ESTSAUTH", None)])
+            .unwrap();
+        let error = CookieJar::new(&path, "other").load().unwrap_err();
+        assert!(error.to_string().contains("wrong key?"), "{error}");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_tampered_and_truncated_files() {
+        let path = jar_path("tampered");
+        let jar = CookieJar::new(&path, "passphrase");
+        jar.save(&[cookie("ESTSAUTH", None)]).unwrap();
+        let mut data = fs::read(&path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        fs::write(&path, &data).unwrap();
+        assert!(jar.load().is_err());
+
+        fs::write(&path, &data[..SALT_LEN]).unwrap();
+        let error = jar.load().unwrap_err();
+        assert!(error.to_string().contains("is truncated"), "{error}");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,406 @@
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use headless_chrome::browser::tab::RequestPausedDecision;
+use headless_chrome::browser::transport::{SessionId, Transport};
+use headless_chrome::protocol::cdp::types::Event;
+use headless_chrome::protocol::cdp::Fetch::events::RequestPausedEvent;
+use headless_chrome::protocol::cdp::Fetch::{GetResponseBody, RequestPattern, RequestStage};
+use headless_chrome::protocol::cdp::Network::{CookieParam, GetAllCookies, Headers, ResourceType};
+use headless_chrome::protocol::cdp::Page::CaptureScreenshotFormatOption;
+use headless_chrome::{Browser, LaunchOptionsBuilder, Tab};
+use serde_json::{json, Value};
+
+use crate::driver::{Cookie, Driver, InterceptedResponse, Page, ResponseLog};
+use crate::har::{HarRecorder, RecordedRequest, RecordedResponse};
+
+/// Driver backed by a Chrome instance controlled through headless_chrome
+pub struct ChromeDriver {
+    pub browser: Browser,
+}
+
+impl ChromeDriver {
+    pub fn new(browser: Browser) -> Self {
+        ChromeDriver { browser }
+    }
+
+    /// Launches Chrome, or connects to the running Chrome at `remote_url` if one is configured
+    pub fn launch(config: &ChromeConfig) -> Result<Self> {
+        if let Some(url) = &config.remote_url {
+            let browser = Browser::connect_with_timeout(url.clone(), config.idle_timeout)?;
+            browser.register_missing_tabs();
+            return Ok(ChromeDriver::new(browser));
+        }
+        let mut args: Vec<String> = config.args.clone();
+        if let Some(user_agent) = &config.user_agent {
+            args.push(format!("--user-agent={user_agent}"));
+        }
+        let launch_options = LaunchOptionsBuilder::default()
+            .headless(config.headless)
+            .sandbox(config.sandbox)
+            .path(config.path.clone())
+            .user_data_dir(config.user_data_dir.clone())
+            .window_size(config.window_size)
+            .idle_browser_timeout(config.idle_timeout)
+            .args(args.iter().map(OsStr::new).collect())
+            .build()?;
+        Ok(ChromeDriver::new(Browser::new(launch_options)?))
+    }
+}
+
+/// How Chrome is launched
+#[derive(Clone, Debug)]
+pub struct ChromeConfig {
+    pub headless: bool,
+    /// Chrome executable, found or fetched automatically when `None`
+    pub path: Option<PathBuf>,
+    /// Profile directory kept between runs, a fresh temporary profile is used when `None`
+    pub user_data_dir: Option<PathBuf>,
+    pub window_size: Option<(u32, u32)>,
+    pub user_agent: Option<String>,
+    pub sandbox: bool,
+    /// Extra command line arguments passed to Chrome
+    pub args: Vec<String>,
+    /// DevTools WebSocket URL of an already running Chrome to attach to instead of launching one
+    pub remote_url: Option<String>,
+    /// How long the browser may go without sending events before the connection is dropped
+    pub idle_timeout: Duration,
+}
+
+impl Default for ChromeConfig {
+    fn default() -> Self {
+        ChromeConfig {
+            headless: false,
+            path: None,
+            user_data_dir: None,
+            window_size: None,
+            user_agent: None,
+            sandbox: true,
+            args: Vec::new(),
+            remote_url: None,
+            idle_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+fn parse_bool(name: &str, value: &str) -> Result<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err(anyhow!("`{name}` expects true or false, found `{value}`")),
+    }
+}
+
+impl ChromeConfig {
+    /// Reads the `CHROME_*` variables, keeping the defaults for the ones that are not set
+    pub fn from_env() -> Result<Self> {
+        let mut config = ChromeConfig::default();
+        if let Ok(headless) = dotenv::var("CHROME_HEADLESS") {
+            config.headless = parse_bool("CHROME_HEADLESS", &headless)?;
+        }
+        if let Ok(path) = dotenv::var("CHROME_PATH") {
+            config.path = Some(PathBuf::from(path));
+        }
+        if let Ok(dir) = dotenv::var("CHROME_USER_DATA_DIR") {
+            config.user_data_dir = Some(PathBuf::from(dir));
+        }
+        if let Ok(size) = dotenv::var("CHROME_WINDOW_SIZE") {
+            let (width, height) = size.split_once('x').ok_or_else(|| {
+                anyhow!("`CHROME_WINDOW_SIZE` expects WIDTHxHEIGHT, found `{size}`")
+            })?;
+            config.window_size = Some((width.trim().parse()?, height.trim().parse()?));
+        }
+        if let Ok(user_agent) = dotenv::var("CHROME_USER_AGENT") {
+            config.user_agent = Some(user_agent);
+        }
+        if let Ok(sandbox) = dotenv::var("CHROME_SANDBOX") {
+            config.sandbox = parse_bool("CHROME_SANDBOX", &sandbox)?;
+        }
+        if let Ok(args) = dotenv::var("CHROME_ARGS") {
+            config.args = args.split_whitespace().map(str::to_string).collect();
+        }
+        if let Ok(url) = dotenv::var("CHROME_REMOTE_URL") {
+            config.remote_url = Some(url);
+        }
+        if let Ok(timeout) = dotenv::var("CHROME_IDLE_TIMEOUT") {
+            config.idle_timeout = Duration::try_from_secs_f64(timeout.parse()?)?;
+        }
+        Ok(config)
+    }
+}
+
+impl Driver for ChromeDriver {
+    fn new_page(&self) -> Result<Arc<dyn Page>> {
+        Ok(Arc::new(ChromePage::new(self.browser.new_tab()?)))
+    }
+
+    fn existing_page(&self, url_prefix: &str) -> Result<Option<Arc<dyn Page>>> {
+        let tabs = self
+            .browser
+            .get_tabs()
+            .lock()
+            .map_err(|_| anyhow!("Poison error"))?;
+        Ok(tabs
+            .iter()
+            .find(|tab| tab.get_url().starts_with(url_prefix))
+            .map(|tab| Arc::new(ChromePage::new(tab.clone())) as Arc<dyn Page>))
+    }
+}
+
+pub struct ChromePage {
+    pub tab: Arc<Tab>,
+    console: Arc<Mutex<Vec<String>>>,
+}
+
+impl ChromePage {
+    pub fn new(tab: Arc<Tab>) -> Self {
+        let console = Arc::new(Mutex::new(Vec::new()));
+        if let Err(e) = record_console(&tab, console.clone()) {
+            eprintln!("Failed to record console messages: {e}");
+        }
+        ChromePage { tab, console }
+    }
+}
+
+fn header_pairs(headers: &Headers) -> Vec<(String, String)> {
+    match &headers.0 {
+        Some(Value::Object(headers)) => headers
+            .iter()
+            .map(|(name, value)| match value {
+                Value::String(value) => (name.clone(), value.clone()),
+                value => (name.clone(), value.to_string()),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn record_console(tab: &Arc<Tab>, console: Arc<Mutex<Vec<String>>>) -> Result<()> {
+    tab.enable_runtime()?;
+    tab.enable_log()?;
+    tab.add_event_listener(Arc::new(move |event: &Event| {
+        let message = match event {
+            Event::RuntimeConsoleAPICalled(event) => {
+                let args = event
+                    .params
+                    .args
+                    .iter()
+                    .map(|arg| match (&arg.value, &arg.description) {
+                        (Some(Value::String(value)), _) => value.clone(),
+                        (Some(value), _) => value.to_string(),
+                        (None, Some(description)) => description.clone(),
+                        (None, None) => String::new(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                format!("[console.{:?}] {args}", event.params.Type)
+            }
+            Event::LogEntryAdded(event) => {
+                let entry = &event.params.entry;
+                format!("[{:?}] {}", entry.level, entry.text)
+            }
+            _ => return,
+        };
+        if let Ok(mut console) = console.lock() {
+            console.push(message);
+        }
+    }))?;
+    Ok(())
+}
+
+impl Page for ChromePage {
+    fn navigate_to(&self, url: &str) -> Result<()> {
+        self.tab.navigate_to(url)?.wait_until_navigated()?;
+        Ok(())
+    }
+
+    fn wait_until_navigated(&self) -> Result<()> {
+        self.tab.wait_until_navigated()?;
+        Ok(())
+    }
+
+    fn url(&self) -> String {
+        self.tab.get_url()
+    }
+
+    fn find_element(&self, selector: &str) -> Result<()> {
+        self.tab.find_element(selector)?;
+        Ok(())
+    }
+
+    fn wait_for_element(&self, selector: &str) -> Result<()> {
+        self.tab.wait_for_element(selector)?;
+        Ok(())
+    }
+
+    fn type_into(&self, selector: &str, text: &str) -> Result<()> {
+        self.tab.find_element(selector)?.type_into(text)?;
+        Ok(())
+    }
+
+    fn press_key(&self, key: &str) -> Result<()> {
+        self.tab.press_key(key)?;
+        Ok(())
+    }
+
+    fn click(&self, selector: &str) -> Result<()> {
+        self.tab.find_element(selector)?.click()?;
+        Ok(())
+    }
+
+    fn inner_text(&self, selector: &str) -> Result<String> {
+        self.tab.find_element(selector)?.get_inner_text()
+    }
+
+    fn evaluate(&self, script: &str) -> Result<Option<Value>> {
+        Ok(self.tab.evaluate(script, true)?.value)
+    }
+
+    fn cookies(&self) -> Result<Vec<Cookie>> {
+        // `Tab::get_cookies` only returns the cookies of the current page, which would leave out
+        // the sign-in's cookies
+        Ok(self
+            .tab
+            .call_method(GetAllCookies(None))?
+            .cookies
+            .into_iter()
+            .map(|cookie| Cookie {
+                expires: (!cookie.session).then_some(cookie.expires),
+                name: cookie.name,
+                value: cookie.value,
+                domain: cookie.domain,
+                path: cookie.path,
+                secure: cookie.secure,
+                http_only: cookie.http_only,
+            })
+            .collect())
+    }
+
+    fn set_cookies(&self, cookies: Vec<Cookie>) -> Result<()> {
+        let params = cookies
+            .into_iter()
+            .map(|cookie| {
+                serde_json::from_value::<CookieParam>(json!({
+                    "name": cookie.name,
+                    "value": cookie.value,
+                    "domain": cookie.domain,
+                    "path": cookie.path,
+                    "expires": cookie.expires,
+                    "secure": cookie.secure,
+                    "httpOnly": cookie.http_only,
+                }))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.tab.set_cookies(params)
+    }
+
+    fn screenshot(&self) -> Result<Vec<u8>> {
+        self.tab
+            .capture_screenshot(CaptureScreenshotFormatOption::Png, None, None, true)
+    }
+
+    fn html(&self) -> Result<String> {
+        self.tab.get_content()
+    }
+
+    fn console_messages(&self) -> Vec<String> {
+        self.console
+            .lock()
+            .map(|console| console.clone())
+            .unwrap_or_default()
+    }
+
+    fn close(&self) -> Result<()> {
+        self.tab.close(false)?;
+        Ok(())
+    }
+
+    fn record_network(&self, recorder: HarRecorder) -> Result<()> {
+        let responses = recorder.clone();
+        // Also enables the network domain the request events below come from
+        self.tab.register_response_handling(
+            "har",
+            Box::new(move |params, fetch_body| {
+                let response = &params.response;
+                let json_xhr = matches!(params.Type, ResourceType::Xhr | ResourceType::Fetch)
+                    && response.mime_type.contains("json");
+                let body = match json_xhr {
+                    true => fetch_body()
+                        .ok()
+                        .filter(|body| !body.base_64_encoded)
+                        .map(|body| body.body),
+                    false => None,
+                };
+                responses.response(RecordedResponse {
+                    id: params.request_id.clone(),
+                    status: response.status,
+                    status_text: response.status_text.clone(),
+                    headers: header_pairs(&response.headers),
+                    mime_type: response.mime_type.clone(),
+                    body,
+                    timestamp: params.timestamp,
+                });
+            }),
+        )?;
+        self.tab.add_event_listener(Arc::new(move |event: &Event| {
+            let Event::NetworkRequestWillBeSent(event) = event else {
+                return;
+            };
+            let params = &event.params;
+            recorder.request(RecordedRequest {
+                id: params.request_id.clone(),
+                method: params.request.method.clone(),
+                url: params.request.url.clone(),
+                headers: header_pairs(&params.request.headers),
+                post_data: params.request.post_data.clone(),
+                started: DateTime::from_timestamp_millis((params.wall_time * 1000.0) as i64)
+                    .unwrap_or_else(Utc::now),
+                timestamp: params.timestamp,
+            });
+        }))?;
+        Ok(())
+    }
+    fn intercept_responses(&self, url_pattern: &str, log: ResponseLog) -> Result<()> {
+        self.tab.enable_fetch(
+            Some(&[RequestPattern {
+                url_pattern: Some(url_pattern.to_string()),
+                resource_Type: None,
+                request_stage: Some(RequestStage::Response),
+            }]),
+            None,
+        )?;
+        self.tab.enable_request_interception(Arc::new(
+            move |transport: Arc<Transport>, session_id: SessionId, event: RequestPausedEvent| {
+                let params = &event.params;
+                let body = transport.call_method_on_target(
+                    session_id,
+                    GetResponseBody {
+                        request_id: params.request_id.clone(),
+                    },
+                );
+                let body = body.ok().and_then(|body| match body.base_64_encoded {
+                    true => base64::engine::general_purpose::STANDARD
+                        .decode(body.body)
+                        .ok()
+                        .and_then(|bytes| String::from_utf8(bytes).ok()),
+                    false => Some(body.body),
+                });
+                match body {
+                    Some(body) => log.push(InterceptedResponse {
+                        url: params.request.url.clone(),
+                        status: params.response_status_code.unwrap_or_default(),
+                        body,
+                    }),
+                    None => eprintln!("Failed to read the response to {}", params.request.url),
+                }
+                RequestPausedDecision::Continue(None)
+            },
+        ))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,567 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+
+use crate::driver::{Cookie, Driver, InterceptedResponse, Page, ResponseLog};
+
+/// Cookie standing for a signed in session, so it can be saved and restored like a real one
+const SESSION_COOKIE: &str = "mock_session";
+
+#[derive(Clone, Debug, PartialEq)]
+enum Trigger {
+    Click(String),
+    /// Enter pressed while the given field is focused
+    Submit(String),
+}
+
+#[derive(Clone, Debug)]
+struct Transition {
+    trigger: Trigger,
+    to: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MockScreen {
+    url: String,
+    elements: HashMap<String, String>,
+    transitions: Vec<Transition>,
+    /// Responses to requests sent when the selector is clicked
+    responses: Vec<(String, InterceptedResponse)>,
+    requires_sign_in: bool,
+    signs_in: bool,
+}
+
+impl MockScreen {
+    pub fn new(url: &str) -> Self {
+        MockScreen {
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Adds an element matched by `selector` with the given inner text
+    pub fn element(mut self, selector: &str, text: &str) -> Self {
+        self.elements.insert(selector.to_string(), text.to_string());
+        self
+    }
+
+    pub fn on_click(mut self, selector: &str, to: &str) -> Self {
+        self.transitions.push(Transition {
+            trigger: Trigger::Click(selector.to_string()),
+            to: to.to_string(),
+        });
+        self
+    }
+
+    pub fn on_submit(mut self, selector: &str, to: &str) -> Self {
+        self.transitions.push(Transition {
+            trigger: Trigger::Submit(selector.to_string()),
+            to: to.to_string(),
+        });
+        self
+    }
+
+    /// Clicking `selector` sends a request to `url` answered with `body`
+    pub fn on_click_respond(mut self, selector: &str, url: &str, body: &str) -> Self {
+        self.responses.push((
+            selector.to_string(),
+            InterceptedResponse {
+                url: url.to_string(),
+                status: 200,
+                body: body.to_string(),
+            },
+        ));
+        self
+    }
+
+    /// Navigating here before signing in shows the script's sign-in screen instead
+    pub fn requires_sign_in(mut self) -> Self {
+        self.requires_sign_in = true;
+        self
+    }
+
+    /// Reaching this screen completes the sign-in
+    pub fn signs_in(mut self) -> Self {
+        self.signs_in = true;
+        self
+    }
+}
+
+/// In-memory scripted browser, used to test the tasks offline.
+///
+/// A script is a set of named screens, each with a URL, the elements on it and the transitions
+/// clicking an element or pressing enter in a field trigger.
+#[derive(Clone, Debug, Default)]
+pub struct MockScript {
+    screens: Arc<HashMap<String, MockScreen>>,
+    sign_in_screen: Option<String>,
+}
+
+impl MockScript {
+    pub fn screen(mut self, name: &str, screen: MockScreen) -> Self {
+        Arc::make_mut(&mut self.screens).insert(name.to_string(), screen);
+        self
+    }
+
+    pub fn sign_in_screen(mut self, name: &str) -> Self {
+        self.sign_in_screen = Some(name.to_string());
+        self
+    }
+
+    /// Microsoft sign-in with email, password, OTP and "Stay signed in?" pages in front of the
+    /// WebAdvisor plan page, which can be moved between `terms` starting at the first term.
+    /// Registering answers that a section was registered.
+    pub fn webadvisor(plan_url: &str, terms: &[String]) -> Self {
+        let register_url = format!("{plan_url}/RegisterSections");
+        let registered = json!({
+            "Messages": [{ "SectionId": "CIS*2500*0101", "Message": "Registered for CIS*2500*0101" }]
+        })
+        .to_string();
+        let login_url = "https://login.microsoftonline.com/common/oauth2/authorize";
+        let mut script = MockScript::default()
+            .screen(
+                "email",
+                MockScreen::new(login_url)
+                    .element("input[type='email']", "")
+                    .on_submit("input[type='email']", "password"),
+            )
+            .screen(
+                "password",
+                MockScreen::new(login_url)
+                    .element("input[type='password']", "")
+                    .on_submit("input[type='password']", "otp"),
+            )
+            .screen(
+                "otp",
+                MockScreen::new(login_url)
+                    .element("#idTxtBx_SAOTCC_OTC", "")
+                    .on_submit("#idTxtBx_SAOTCC_OTC", "stay_signed_in"),
+            )
+            .screen(
+                "stay_signed_in",
+                MockScreen::new(login_url)
+                    .element("#KmsiCheckboxField", "")
+                    .element("#idSIButton9", "Yes")
+                    .on_click("#idSIButton9", "term_0")
+                    .signs_in(),
+            )
+            .sign_in_screen("email");
+        for (i, term) in terms.iter().enumerate() {
+            let mut screen = MockScreen::new(plan_url)
+                .element("#schedule-prev-term", "")
+                .element("#schedule-next-term", "")
+                .element("#schedule-activeterm-text", term)
+                .element("#register-button", "Register Now")
+                .on_click_respond("#register-button", &register_url, &registered)
+                .requires_sign_in();
+            if i > 0 {
+                screen = screen.on_click("#schedule-prev-term", &format!("term_{}", i - 1));
+            }
+            if i + 1 < terms.len() {
+                screen = screen.on_click("#schedule-next-term", &format!("term_{}", i + 1));
+            }
+            script = script.screen(&format!("term_{i}"), screen);
+        }
+        script
+    }
+}
+
+pub struct MockDriver {
+    script: MockScript,
+    pages: Mutex<Vec<Arc<MockPage>>>,
+}
+
+impl MockDriver {
+    pub fn new(script: MockScript) -> Self {
+        MockDriver {
+            script,
+            pages: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Number of pages opened and not closed yet
+    pub fn open_pages(&self) -> usize {
+        let pages = self.pages.lock().unwrap();
+        pages
+            .iter()
+            .filter(|page| !page.state.lock().unwrap().closed)
+            .count()
+    }
+}
+
+impl Driver for MockDriver {
+    fn new_page(&self) -> Result<Arc<dyn Page>> {
+        let page = Arc::new(MockPage::new(self.script.clone()));
+        self.pages.lock().unwrap().push(page.clone());
+        Ok(page)
+    }
+}
+
+#[derive(Default)]
+struct PageState {
+    screen: Option<String>,
+    signed_in: bool,
+    focused: Option<String>,
+    /// URL patterns and the logs their responses go to
+    interceptions: Vec<(String, ResponseLog)>,
+    closed: bool,
+}
+
+pub struct MockPage {
+    script: MockScript,
+    state: Mutex<PageState>,
+}
+
+impl MockPage {
+    pub fn new(script: MockScript) -> Self {
+        MockPage {
+            script,
+            state: Mutex::new(PageState::default()),
+        }
+    }
+
+    fn current<'a>(&'a self, state: &PageState) -> Result<&'a MockScreen> {
+        let name = state
+            .screen
+            .as_ref()
+            .ok_or_else(|| anyhow!("Mock page has not navigated anywhere"))?;
+        self.script
+            .screens
+            .get(name)
+            .ok_or_else(|| anyhow!("Mock script has no screen `{name}`"))
+    }
+
+    fn show(&self, state: &mut PageState, name: &str) -> Result<()> {
+        let screen = self
+            .script
+            .screens
+            .get(name)
+            .ok_or_else(|| anyhow!("Mock script has no screen `{name}`"))?;
+        state.signed_in |= screen.signs_in;
+        state.screen = Some(name.to_string());
+        state.focused = None;
+        Ok(())
+    }
+
+    fn trigger(&self, state: &mut PageState, trigger: Trigger) -> Result<()> {
+        let target = self
+            .current(state)?
+            .transitions
+            .iter()
+            .find(|transition| transition.trigger == trigger)
+            .map(|transition| transition.to.clone());
+        match target {
+            Some(target) => self.show(state, &target),
+            None => Ok(()),
+        }
+    }
+
+    fn require(&self, state: &PageState, selector: &str) -> Result<()> {
+        if self.current(state)?.elements.contains_key(selector) {
+            Ok(())
+        } else {
+            Err(anyhow!("Could not find element `{selector}`"))
+        }
+    }
+}
+
+impl Page for MockPage {
+    fn navigate_to(&self, url: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        println!("[mock] navigate {url}");
+        let (name, screen) = self
+            .script
+            .screens
+            .iter()
+            .filter(|(_, screen)| screen.url == url)
+            .min_by_key(|(name, _)| name.as_str())
+            .ok_or_else(|| anyhow!("Mock script has no screen for {url}"))?;
+        let name = match (&self.script.sign_in_screen, state.signed_in) {
+            (Some(sign_in), false) if screen.requires_sign_in => sign_in.clone(),
+            _ => name.clone(),
+        };
+        self.show(&mut state, &name)
+    }
+
+    fn wait_until_navigated(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn url(&self) -> String {
+        let state = self.state.lock().unwrap();
+        self.current(&state)
+            .map(|screen| screen.url.clone())
+            .unwrap_or_else(|_| "about:blank".to_string())
+    }
+
+    fn find_element(&self, selector: &str) -> Result<()> {
+        let state = self.state.lock().unwrap();
+        self.require(&state, selector)
+    }
+
+    fn wait_for_element(&self, selector: &str) -> Result<()> {
+        self.find_element(selector)
+    }
+
+    fn type_into(&self, selector: &str, text: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        self.require(&state, selector)?;
+        println!("[mock] type {} characters into {selector}", text.len());
+        state.focused = Some(selector.to_string());
+        Ok(())
+    }
+
+    fn press_key(&self, key: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        println!("[mock] press {key}");
+        match (key, state.focused.clone()) {
+            ("Enter", Some(focused)) => self.trigger(&mut state, Trigger::Submit(focused)),
+            _ => Ok(()),
+        }
+    }
+
+    fn click(&self, selector: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        self.require(&state, selector)?;
+        println!("[mock] click {selector}");
+        for (clicked, response) in self.current(&state)?.responses.iter() {
+            if clicked != selector {
+                continue;
+            }
+            for (pattern, log) in state.interceptions.iter() {
+                if url_matches(pattern, &response.url) {
+                    log.push(response.clone());
+                }
+            }
+        }
+        self.trigger(&mut state, Trigger::Click(selector.to_string()))
+    }
+
+    fn inner_text(&self, selector: &str) -> Result<String> {
+        let state = self.state.lock().unwrap();
+        self.require(&state, selector)?;
+        Ok(self.current(&state)?.elements[selector].clone())
+    }
+
+    fn evaluate(&self, script: &str) -> Result<Option<Value>> {
+        println!("[mock] evaluate {}", script.trim());
+        Ok(None)
+    }
+
+    fn cookies(&self) -> Result<Vec<Cookie>> {
+        let state = self.state.lock().unwrap();
+        Ok(match state.signed_in {
+            true => vec![session_cookie()],
+            false => Vec::new(),
+        })
+    }
+
+    fn set_cookies(&self, cookies: Vec<Cookie>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        println!("[mock] set {} cookies", cookies.len());
+        state.signed_in |= cookies.iter().any(|cookie| cookie.name == SESSION_COOKIE);
+        Ok(())
+    }
+
+    fn screenshot(&self) -> Result<Vec<u8>> {
+        Err(anyhow!("Mock pages cannot take screenshots"))
+    }
+
+    /// The elements of the current screen, one per line
+    fn html(&self) -> Result<String> {
+        let state = self.state.lock().unwrap();
+        let screen = self.current(&state)?;
+        let mut selectors: Vec<&String> = screen.elements.keys().collect();
+        selectors.sort();
+        Ok(selectors
+            .into_iter()
+            .map(|selector| format!("<!-- {selector} -->{}\n", screen.elements[selector]))
+            .collect())
+    }
+
+    fn console_messages(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn close(&self) -> Result<()> {
+        println!("[mock] close");
+        self.state.lock().unwrap().closed = true;
+        Ok(())
+    }
+
+    fn intercept_responses(&self, url_pattern: &str, log: ResponseLog) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.interceptions.push((url_pattern.to_string(), log));
+        Ok(())
+    }
+}
+
+fn session_cookie() -> Cookie {
+    Cookie {
+        name: SESSION_COOKIE.to_string(),
+        value: "1".to_string(),
+        domain: "localhost".to_string(),
+        path: "/".to_string(),
+        expires: None,
+        secure: true,
+        http_only: true,
+    }
+}
+
+/// Whether `url` matches `pattern`, where `*` matches any run of characters
+fn url_matches(pattern: &str, url: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = url.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Sets the variables the tasks read to the values the tests expect
+pub(crate) fn test_env() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| {
+        std::env::set_var("EMAIL", "student@uoguelph.ca");
+        std::env::set_var("PASSWORD", "password");
+        std::env::set_var("OTP", "JBSWY3DPEHPK3PXP");
+        std::env::set_var("TARGET_SEMESTER", "Fall 2025");
+    });
+}
+
+/// Context with a browser running `script`, navigated to `url` with or without a session
+pub(crate) fn mock_context(
+    script: MockScript,
+    url: &str,
+    signed_in: bool,
+) -> crate::context::Context {
+    test_env();
+    let driver = MockDriver::new(script);
+    let page = driver.new_page().unwrap();
+    if signed_in {
+        page.set_cookies(vec![session_cookie()]).unwrap();
+    }
+    page.navigate_to(url).unwrap();
+    let context = crate::context::Context::default();
+    context
+        .set_inner(Some(crate::context::ContextInner {
+            driver: Arc::new(driver),
+            page,
+        }))
+        .unwrap();
+    context
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAN_URL: &str = "https://wa.test/Student/Planning/DegreePlans";
+
+    fn script() -> MockScript {
+        let terms = vec!["Winter 2025".to_string(), "Fall 2025".to_string()];
+        MockScript::webadvisor(PLAN_URL, &terms)
+    }
+
+    #[test]
+    fn url_matches_globs() {
+        assert!(url_matches(
+            "*/RegisterSections*",
+            &format!("{PLAN_URL}/RegisterSections")
+        ));
+        assert!(url_matches(
+            "*/RegisterSections*",
+            "https://wa.test/RegisterSections?x=1"
+        ));
+        assert!(url_matches("https://wa.test/*", "https://wa.test/"));
+        assert!(url_matches(
+            "https://*.test/*/Plans",
+            "https://wa.test/Student/Plans"
+        ));
+        assert!(url_matches("exact", "exact"));
+        assert!(!url_matches("exact", "exactly"));
+        assert!(!url_matches(
+            "*/RegisterSections",
+            "https://wa.test/CurrentAsync"
+        ));
+        assert!(!url_matches("https://wa.test/*", "http://wa.test/"));
+        assert!(!url_matches("*a*a*", "a"));
+    }
+
+    #[test]
+    fn plan_requires_sign_in() {
+        let page = MockPage::new(script());
+        page.navigate_to(PLAN_URL).unwrap();
+        assert!(page.find_element("input[type='email']").is_ok());
+        assert!(page.find_element("#register-button").is_err());
+        assert!(page.cookies().unwrap().is_empty());
+    }
+
+    #[test]
+    fn restored_cookies_skip_sign_in() {
+        let page = MockPage::new(script());
+        page.set_cookies(vec![session_cookie()]).unwrap();
+        page.navigate_to(PLAN_URL).unwrap();
+        assert_eq!(
+            page.inner_text("#schedule-activeterm-text").unwrap(),
+            "Winter 2025"
+        );
+        page.click("#schedule-next-term").unwrap();
+        assert_eq!(
+            page.inner_text("#schedule-activeterm-text").unwrap(),
+            "Fall 2025"
+        );
+        // The last term has nowhere further to go
+        page.click("#schedule-next-term").unwrap();
+        assert_eq!(
+            page.inner_text("#schedule-activeterm-text").unwrap(),
+            "Fall 2025"
+        );
+    }
+
+    #[test]
+    fn register_click_reaches_interceptions() {
+        let page = MockPage::new(script());
+        page.set_cookies(signed_in_cookies()).unwrap();
+        page.navigate_to(PLAN_URL).unwrap();
+        let log = ResponseLog::default();
+        let other = ResponseLog::default();
+        page.intercept_responses("*/RegisterSections*", log.clone())
+            .unwrap();
+        page.intercept_responses("*/CurrentAsync*", other.clone())
+            .unwrap();
+        page.click("#register-button").unwrap();
+        let responses = log.take();
+        assert_eq!(responses.len(), 1);
+        assert!(responses[0].body.contains("Registered for CIS*2500*0101"));
+        assert!(other.take().is_empty());
+    }
+
+    /// Cookies of a page that went through the whole sign-in
+    fn signed_in_cookies() -> Vec<Cookie> {
+        let page = MockPage::new(script());
+        page.navigate_to(PLAN_URL).unwrap();
+        page.type_into("input[type='email']", "student@uoguelph.ca")
+            .unwrap();
+        page.press_key("Enter").unwrap();
+        page.type_into("input[type='password']", "password")
+            .unwrap();
+        page.press_key("Enter").unwrap();
+        page.type_into("#idTxtBx_SAOTCC_OTC", "123456").unwrap();
+        page.press_key("Enter").unwrap();
+        page.click("#idSIButton9").unwrap();
+        page.cookies().unwrap()
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::har::HarRecorder;
+
+pub mod chrome;
+#[cfg(test)]
+pub mod mock;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    pub path: String,
+    /// Seconds since the epoch, `None` for session cookies
+    pub expires: Option<f64>,
+    pub secure: bool,
+    pub http_only: bool,
+}
+
+/// Response to a request matched by [`Page::intercept_responses`]
+#[derive(Clone, Debug)]
+pub struct InterceptedResponse {
+    pub url: String,
+    pub status: u32,
+    pub body: String,
+}
+
+/// Intercepted responses waiting to be read, shared with the page recording them
+#[derive(Clone, Default)]
+pub struct ResponseLog {
+    responses: Arc<Mutex<Vec<InterceptedResponse>>>,
+}
+
+impl ResponseLog {
+    pub fn push(&self, response: InterceptedResponse) {
+        self.responses.lock().unwrap().push(response);
+    }
+
+    /// Responses received since the last call, oldest first
+    pub fn take(&self) -> Vec<InterceptedResponse> {
+        std::mem::take(&mut *self.responses.lock().unwrap())
+    }
+}
+
+/// A single browser tab, addressed by CSS selectors so tasks don't depend on a specific browser
+/// automation library
+pub trait Page: Send + Sync {
+    /// Navigates to `url` and waits for the navigation to finish
+    fn navigate_to(&self, url: &str) -> Result<()>;
+    fn wait_until_navigated(&self) -> Result<()>;
+    fn url(&self) -> String;
+    /// Fails if no element matches `selector`
+    fn find_element(&self, selector: &str) -> Result<()>;
+    /// Waits for an element matching `selector` to appear, failing after a timeout
+    fn wait_for_element(&self, selector: &str) -> Result<()>;
+    /// Focuses the element and types `text` into it
+    fn type_into(&self, selector: &str, text: &str) -> Result<()>;
+    /// Presses a key on the focused element
+    fn press_key(&self, key: &str) -> Result<()>;
+    fn click(&self, selector: &str) -> Result<()>;
+    fn inner_text(&self, selector: &str) -> Result<String>;
+    /// Evaluates JavaScript on the page, returning its value if it has one
+    fn evaluate(&self, script: &str) -> Result<Option<Value>>;
+    /// Cookies of the whole browser, not only the current page
+    fn cookies(&self) -> Result<Vec<Cookie>>;
+    fn set_cookies(&self, cookies: Vec<Cookie>) -> Result<()>;
+    /// PNG screenshot of the visible part of the page
+    fn screenshot(&self) -> Result<Vec<u8>>;
+    /// Outer HTML of the whole document
+    fn html(&self) -> Result<String>;
+    /// Console messages logged since the page was opened
+    fn console_messages(&self) -> Vec<String>;
+    /// Closes the tab, the page can't be used afterwards
+    fn close(&self) -> Result<()>;
+
+    /// Records the page's network traffic into `recorder` from now on
+    fn record_network(&self, _recorder: HarRecorder) -> Result<()> {
+        Ok(())
+    }
+
+    /// Pushes the responses to requests whose URL matches `url_pattern` (`*` matching any run of
+    /// characters) into `log` from now on
+    fn intercept_responses(&self, _url_pattern: &str, _log: ResponseLog) -> Result<()> {
+        Err(anyhow!("This page cannot intercept responses"))
+    }
+}
+
+/// A browser able to open pages
+pub trait Driver: Send + Sync {
+    fn new_page(&self) -> Result<Arc<dyn Page>>;
+
+    /// A page the browser already had open whose URL starts with `url_prefix`
+    fn existing_page(&self, _url_prefix: &str) -> Result<Option<Arc<dyn Page>>> {
+        Ok(None)
+    }
+}
@@ -0,0 +1,401 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use petgraph::prelude::*;
+use tokio::task::JoinSet;
+use tokio::time;
+use tokio::time::sleep;
+
+use crate::artifacts::Artifacts;
+use crate::branch::resolve_branch;
+use crate::checkpoint::Checkpoint;
+use crate::context::Context;
+use crate::report::{RunReport, TaskOutcome, TaskStatus};
+use crate::task::Task;
+use crate::TaskEdge;
+
+/// How a branch of the graph ended
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BranchEnd {
+    /// A terminal task completed
+    Terminal,
+    /// A forked branch reached a join node, which is left for the forking branch to run
+    Join(NodeIndex),
+    Failed,
+}
+
+/// Runs the task graph, following one outgoing edge at a time and running the successors of
+/// fork nodes concurrently
+pub struct Executor {
+    graph: DiGraph<Task, TaskEdge>,
+    report: Mutex<RunReport>,
+    report_path: String,
+    checkpoint: Mutex<Checkpoint>,
+}
+
+impl Executor {
+    pub fn new(graph: DiGraph<Task, TaskEdge>, report_path: String) -> Arc<Self> {
+        Arc::new(Executor {
+            graph,
+            report: Mutex::new(RunReport::default()),
+            report_path,
+            checkpoint: Mutex::new(Checkpoint::default()),
+        })
+    }
+
+    pub fn graph(&self) -> &DiGraph<Task, TaskEdge> {
+        &self.graph
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.checkpoint.lock().unwrap().clone()
+    }
+
+    /// Runs the graph from `from` until a terminal task completes or a task fails
+    pub async fn run(self: Arc<Self>, ctx: Context, from: NodeIndex) -> BranchEnd {
+        self.run_branch(ctx, from, false).await
+    }
+
+    fn record(&self, node: NodeIndex, outcome: TaskOutcome) {
+        let mut report = self.report.lock().unwrap();
+        report.record(&self.graph[node].name, outcome);
+        if let Err(e) = report.save(&self.report_path) {
+            eprintln!("Failed to save run report: {e}");
+        }
+    }
+
+    fn run_branch(
+        self: Arc<Self>,
+        ctx: Context,
+        from: NodeIndex,
+        forked: bool,
+    ) -> Pin<Box<dyn Future<Output = BranchEnd> + Send>> {
+        Box::pin(async move {
+            let graph = &self.graph;
+            let mut node = from;
+            // Guard of the edge `node` was reached through, kept so a resume checks it again
+            let mut guard = match forked {
+                true => None,
+                false => self.checkpoint.lock().unwrap().guard_of(from),
+            };
+            loop {
+                println!("Running {:?}", graph[node].name);
+                let started = time::Instant::now();
+                let status = execute_task(graph, ctx.clone(), node).await;
+                self.record(node, TaskOutcome::new(status, started.elapsed()));
+                if status != TaskStatus::Succeeded {
+                    return BranchEnd::Failed;
+                }
+                // Forked branches run in their own tab, which can't be resumed
+                if !forked {
+                    self.checkpoint.lock().unwrap().record(node, guard.take());
+                }
+                if graph[node].terminal {
+                    println!("Finished at terminal node {:?}", &graph[node].name);
+                    return BranchEnd::Terminal;
+                }
+
+                if graph[node].fork {
+                    match self.clone().fork(&ctx, node).await {
+                        BranchEnd::Join(join) => {
+                            node = join;
+                            continue;
+                        }
+                        end => return end,
+                    }
+                }
+
+                match resolve_branch(graph, &ctx, node).await {
+                    Ok(next) => {
+                        println!("Success!");
+                        if forked && graph[next].join {
+                            return BranchEnd::Join(next);
+                        }
+                        guard = graph
+                            .edges_connecting(node, next)
+                            .find(|edge| edge.weight().is_outgoing())
+                            .and_then(|edge| edge.weight().guard.clone());
+                        node = next;
+                    }
+                    Err(e) => {
+                        eprintln!("Timed out! {e}");
+                        if let Ok(Some(artifacts)) = ctx.get::<Artifacts>() {
+                            artifacts.capture_branch(&ctx, &graph[node].name, &e.to_string());
+                        }
+                        return BranchEnd::Failed;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Starts every successor of `node` concurrently in its own tab and waits for all of them.
+    ///
+    /// The branches must either all finish at terminal tasks, or all stop at the same join node,
+    /// which is then returned for this branch to continue from. Each tab is closed once its
+    /// branch ends, the state store stays shared between the branches.
+    async fn fork(self: Arc<Self>, ctx: &Context, node: NodeIndex) -> BranchEnd {
+        let mut forks = Vec::new();
+        for edge in self.graph.edges(node) {
+            if !edge.weight().is_outgoing() {
+                continue;
+            }
+            match ctx.fork() {
+                Ok(branch_ctx) => forks.push((edge.target(), branch_ctx)),
+                Err(e) => {
+                    eprintln!(
+                        "Failed to open a tab for {:?}: {e}",
+                        self.graph[edge.target()].name
+                    );
+                    for (_, branch_ctx) in forks {
+                        close_tab(&branch_ctx);
+                    }
+                    return BranchEnd::Failed;
+                }
+            }
+        }
+
+        let mut branches = JoinSet::new();
+        for (target, branch_ctx) in forks {
+            println!("Forking {:?}", self.graph[target].name);
+            let branch = self.clone().run_branch(branch_ctx.clone(), target, true);
+            branches.spawn(async move {
+                let end = branch.await;
+                close_tab(&branch_ctx);
+                end
+            });
+        }
+
+        let mut joins = Vec::new();
+        let mut failed = false;
+        while let Some(end) = branches.join_next().await {
+            match end {
+                Ok(BranchEnd::Terminal) => {}
+                Ok(BranchEnd::Join(join)) => joins.push(join),
+                Ok(BranchEnd::Failed) => failed = true,
+                Err(e) => {
+                    eprintln!("Branch panicked: {e}");
+                    failed = true;
+                }
+            }
+        }
+        if failed {
+            return BranchEnd::Failed;
+        }
+        joins.sort();
+        joins.dedup();
+        match joins.as_slice() {
+            [] => BranchEnd::Terminal,
+            [join] => {
+                println!("Joined at {:?}", self.graph[*join].name);
+                BranchEnd::Join(*join)
+            }
+            _ => {
+                eprintln!(
+                    "Branches forked from {:?} joined at different nodes",
+                    self.graph[node].name
+                );
+                BranchEnd::Failed
+            }
+        }
+    }
+}
+
+fn close_tab(ctx: &Context) {
+    if let Err(e) = ctx.page().and_then(|page| page.close()) {
+        eprintln!("Failed to close tab: {e}");
+    }
+}
+
+async fn execute_task(
+    graph: &DiGraph<Task, TaskEdge>,
+    ctx: Context,
+    node: NodeIndex,
+) -> TaskStatus {
+    let policy = &graph[node].policy;
+    let mut status = TaskStatus::Failed;
+    for attempt in 1..=policy.max_attempts {
+        if attempt > 1 {
+            let delay = policy.backoff.delay(attempt - 1);
+            println!(
+                "Retrying node {:?} in {:?} (attempt {attempt}/{})",
+                &graph[node].name, delay, policy.max_attempts
+            );
+            sleep(delay).await;
+        }
+        status = execute_attempt(graph, ctx.clone(), node).await;
+        if status == TaskStatus::Succeeded {
+            break;
+        }
+        if let Ok(Some(artifacts)) = ctx.get::<Artifacts>() {
+            artifacts.capture(&ctx, &graph[node].name, attempt, status);
+        }
+    }
+    status
+}
+
+async fn execute_attempt(
+    graph: &DiGraph<Task, TaskEdge>,
+    ctx: Context,
+    node: NodeIndex,
+) -> TaskStatus {
+    let task = &graph[node];
+    let policy = &task.policy;
+
+    // Retry precondition until timeout
+    let pre_condition_future = async {
+        let start = time::Instant::now();
+        loop {
+            if start.elapsed() >= policy.pre_condition_timeout {
+                return Ok(false);
+            }
+
+            match (task.pre_condition)(ctx.clone()).await {
+                Ok(true) => return Ok(true),
+                Ok(false) => {
+                    tokio::time::sleep(policy.pre_condition_poll).await;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    };
+
+    match time::timeout(policy.pre_condition_timeout, pre_condition_future).await {
+        Ok(Ok(true)) => {
+            let task_future = (task.func)(ctx);
+            match policy.timeout {
+                None => match task_future.await {
+                    Ok(_) => TaskStatus::Succeeded,
+                    Err(e) => {
+                        eprintln!("Failed task {}, {e}", task.name);
+                        TaskStatus::Failed
+                    }
+                },
+                Some(task_timeout) => match time::timeout(task_timeout, task_future).await {
+                    Ok(Ok(_)) => TaskStatus::Succeeded,
+                    Ok(Err(e)) => {
+                        println!(
+                            "Task function failed for node {:?}: {:?}",
+                            &graph[node].name, e
+                        );
+                        TaskStatus::Failed
+                    }
+                    Err(_) => {
+                        println!("Task function timed out for node {:?}", &graph[node].name);
+                        TaskStatus::TimedOut
+                    }
+                },
+            }
+        }
+        Ok(Ok(false)) | Err(_) => {
+            println!(
+                "Precondition check timed out for node {:?}",
+                &graph[node].name
+            );
+            TaskStatus::TimedOut
+        }
+        Ok(Err(e)) => {
+            println!(
+                "Precondition check failed for node {:?}: {:?}",
+                &graph[node].name, e
+            );
+            TaskStatus::Failed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::context::ContextInner;
+    use crate::driver::mock::{MockDriver, MockScript};
+    use crate::driver::Driver;
+
+    const PLAN_URL: &str = "https://wa.test/Student/Planning/DegreePlans";
+
+    /// Names of the tasks which ran, written to by every branch
+    #[derive(Clone, Default)]
+    struct Ran(Vec<String>);
+
+    fn task(name: &str, fails: bool) -> Task {
+        let owned = name.to_string();
+        Task::new_async(
+            name,
+            move |ctx: Context| {
+                let name = owned.clone();
+                async move {
+                    ctx.update(|Ran(ran): &mut Ran| ran.push(name.clone()))?;
+                    match fails {
+                        true => Err(anyhow!("{name} failed")),
+                        false => Ok(()),
+                    }
+                }
+            },
+            |_| async { Ok(true) },
+            false,
+        )
+    }
+
+    /// Fork -> (Left, Right) -> Join, where Right fails if `right_fails`
+    fn executor(right_fails: bool) -> (Arc<Executor>, NodeIndex) {
+        let mut graph = DiGraph::new();
+        let fork = graph.add_node(task("Fork", false).with_fork(true));
+        let left = graph.add_node(task("Left", false));
+        let right = graph.add_node(task("Right", right_fails));
+        let join = graph.add_node(task("Join", false).with_join(true).with_terminal(true));
+        for (source, target) in [(fork, left), (fork, right), (left, join), (right, join)] {
+            graph.add_edge(source, target, TaskEdge::outgoing(0, None));
+        }
+        let report = std::env::temp_dir().join(format!(
+            "executor-{}-{right_fails}.toml",
+            std::process::id()
+        ));
+        let executor = Executor::new(graph, report.to_string_lossy().to_string());
+        (executor, fork)
+    }
+
+    fn context() -> (Context, Arc<MockDriver>) {
+        let terms = vec!["Fall 2025".to_string()];
+        let driver = Arc::new(MockDriver::new(MockScript::webadvisor(PLAN_URL, &terms)));
+        let page = driver.new_page().unwrap();
+        page.navigate_to(PLAN_URL).unwrap();
+        let ctx = Context::default();
+        ctx.set_inner(Some(ContextInner {
+            driver: driver.clone(),
+            page,
+        }))
+        .unwrap();
+        (ctx, driver)
+    }
+
+    fn ran(ctx: &Context) -> Vec<String> {
+        let Ran(mut ran) = ctx.get::<Ran>().unwrap().unwrap();
+        ran.sort();
+        ran
+    }
+
+    #[tokio::test]
+    async fn forks_and_joins_in_the_forking_branch() {
+        let (executor, fork) = executor(false);
+        let (ctx, driver) = context();
+        assert_eq!(executor.run(ctx.clone(), fork).await, BranchEnd::Terminal);
+        // Both branches wrote to the shared store, and the join ran once
+        assert_eq!(ran(&ctx), vec!["Fork", "Join", "Left", "Right"]);
+        assert_eq!(driver.open_pages(), 1);
+    }
+
+    #[tokio::test]
+    async fn failing_branch_fails_the_fork() {
+        let (executor, fork) = executor(true);
+        let (ctx, driver) = context();
+        assert_eq!(executor.run(ctx.clone(), fork).await, BranchEnd::Failed);
+        assert_eq!(ran(&ctx), vec!["Fork", "Left", "Right"]);
+        assert_eq!(driver.open_pages(), 1);
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt::Write;
+
+use anyhow::{anyhow, Result};
+use petgraph::prelude::*;
+
+use crate::report::{RunReport, TaskStatus};
+use crate::task::Task;
+use crate::TaskEdge;
+
+/// Text format the task graph is rendered in
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+impl GraphFormat {
+    pub fn parse(input: &str) -> Result<Self> {
+        match input {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            _ => Err(anyhow!(
+                "Unknown graph format `{input}`, expected `dot` or `mermaid`"
+            )),
+        }
+    }
+}
+
+fn label(task: &Task, report: Option<&RunReport>) -> Vec<String> {
+    let mut lines = vec![task.name.clone()];
+    if task.repeatable {
+        lines.push("repeatable".to_string());
+    }
+    if task.fork {
+        lines.push("fork".to_string());
+    }
+    if task.join {
+        lines.push("join".to_string());
+    }
+    if let Some(outcome) = report.and_then(|r| r.get(&task.name)) {
+        lines.push(outcome.to_string());
+    }
+    lines
+}
+
+fn color(task: &Task, report: Option<&RunReport>) -> Option<&'static str> {
+    report
+        .and_then(|r| r.get(&task.name))
+        .map(|outcome| match outcome.status {
+            TaskStatus::Succeeded => "#b7e1a1",
+            TaskStatus::Failed => "#f4a6a6",
+            TaskStatus::TimedOut => "#f9d89c",
+        })
+}
+
+fn outgoing_edges(
+    graph: &DiGraph<Task, TaskEdge>,
+) -> impl Iterator<Item = (NodeIndex, NodeIndex, String)> + '_ {
+    graph
+        .edge_references()
+        .filter(|edge| edge.weight().is_outgoing())
+        .map(|edge| {
+            let weight = edge.weight();
+            let mut label = weight
+                .guard
+                .as_ref()
+                .map(|guard| guard.name.clone())
+                .unwrap_or_default();
+            if weight.priority != 0 {
+                if !label.is_empty() {
+                    label.push(' ');
+                }
+                write!(label, "priority {}", weight.priority).unwrap();
+            }
+            (edge.source(), edge.target(), label)
+        })
+}
+
+pub fn to_dot(graph: &DiGraph<Task, TaskEdge>, report: Option<&RunReport>) -> String {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut out = String::from("digraph tasks {\n    node [shape=box];\n");
+    for node in graph.node_indices() {
+        let task = &graph[node];
+        let label = label(task, report)
+            .iter()
+            .map(|line| escape(line))
+            .collect::<Vec<_>>()
+            .join("\\n");
+        let mut attributes = format!("label=\"{label}\"");
+        if task.repeatable {
+            attributes.push_str(", peripheries=2");
+        }
+        if let Some(color) = color(task, report) {
+            write!(attributes, ", style=filled, fillcolor=\"{color}\"").unwrap();
+        }
+        writeln!(out, "    n{} [{attributes}];", node.index()).unwrap();
+    }
+    for (source, target, label) in outgoing_edges(graph) {
+        if label.is_empty() {
+            writeln!(out, "    n{} -> n{};", source.index(), target.index()).unwrap();
+        } else {
+            writeln!(
+                out,
+                "    n{} -> n{} [label=\"{}\"];",
+                source.index(),
+                target.index(),
+                escape(&label)
+            )
+            .unwrap();
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+pub fn to_mermaid(graph: &DiGraph<Task, TaskEdge>, report: Option<&RunReport>) -> String {
+    let escape = |s: &str| s.replace('"', "#quot;");
+    let mut out = String::from("flowchart TD\n");
+    for node in graph.node_indices() {
+        let task = &graph[node];
+        let label = label(task, report)
+            .iter()
+            .map(|line| escape(line))
+            .collect::<Vec<_>>()
+            .join("<br/>");
+        if task.repeatable {
+            writeln!(out, "    n{}[[\"{label}\"]]", node.index()).unwrap();
+        } else {
+            writeln!(out, "    n{}[\"{label}\"]", node.index()).unwrap();
+        }
+        if let Some(color) = color(task, report) {
+            writeln!(out, "    style n{} fill:{color}", node.index()).unwrap();
+        }
+    }
+    for (source, target, label) in outgoing_edges(graph) {
+        if label.is_empty() {
+            writeln!(out, "    n{} --> n{}", source.index(), target.index()).unwrap();
+        } else {
+            writeln!(
+                out,
+                "    n{} -->|\"{}\"| n{}",
+                source.index(),
+                escape(&label),
+                target.index()
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+pub fn render(
+    graph: &DiGraph<Task, TaskEdge>,
+    format: GraphFormat,
+    report: Option<&RunReport>,
+) -> String {
+    match format {
+        GraphFormat::Dot => to_dot(graph, report),
+        GraphFormat::Mermaid => to_mermaid(graph, report),
+    }
+}
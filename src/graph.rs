@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use petgraph::prelude::*;
+use serde::Deserialize;
+
+use crate::task::{Backoff, Task, TaskParams, TaskPolicy, TaskRegistry};
+use crate::TaskEdge;
+
+/// Declarative description of the task graph, loaded from a TOML file
+#[derive(Deserialize, Debug)]
+pub struct GraphFile {
+    /// Name of the node execution begins at
+    pub start: String,
+    #[serde(default)]
+    pub nodes: Vec<NodeDef>,
+    #[serde(default)]
+    pub edges: Vec<EdgeDef>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NodeDef {
+    pub name: String,
+    /// Registered task kind, e.g. `browser.open`
+    pub kind: String,
+    #[serde(default)]
+    pub repeatable: bool,
+    /// Marks the node as an intended end of the graph
+    #[serde(default)]
+    pub terminal: bool,
+    /// Starts all outgoing nodes concurrently, each in its own tab
+    #[serde(default)]
+    pub fork: bool,
+    /// Waits for every forked branch to arrive before running
+    #[serde(default)]
+    pub join: bool,
+    /// Skipped nodes are left out and their incoming edges are bridged to their outgoing ones
+    #[serde(default)]
+    pub skip: bool,
+    /// Parameters handed to the task kind, e.g. a URL or CSS selector
+    #[serde(default)]
+    pub params: HashMap<String, String>,
+    /// Overrides of the default task policy
+    #[serde(default)]
+    pub policy: PolicyDef,
+}
+
+/// Task policy overrides, all durations are given in seconds
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyDef {
+    pub pre_condition_timeout: Option<f64>,
+    pub pre_condition_poll: Option<f64>,
+    pub timeout: Option<f64>,
+    pub max_attempts: Option<u32>,
+    pub backoff: Option<BackoffDef>,
+    pub branch_timeout: Option<f64>,
+    pub branch_poll: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BackoffDef {
+    Fixed { delay: f64 },
+    Exponential { initial: f64, max: f64 },
+    Jittered { initial: f64, max: f64 },
+}
+
+impl PolicyDef {
+    fn apply(&self, policy: TaskPolicy) -> Result<TaskPolicy> {
+        let secs = |value: f64| {
+            Duration::try_from_secs_f64(value)
+                .map_err(|e| anyhow!("Invalid duration `{value}` in task policy: {e}"))
+        };
+        let backoff = match self.backoff {
+            None => policy.backoff,
+            Some(BackoffDef::Fixed { delay }) => Backoff::Fixed(secs(delay)?),
+            Some(BackoffDef::Exponential { initial, max }) => Backoff::Exponential {
+                initial: secs(initial)?,
+                max: secs(max)?,
+            },
+            Some(BackoffDef::Jittered { initial, max }) => Backoff::Jittered {
+                initial: secs(initial)?,
+                max: secs(max)?,
+            },
+        };
+        if self.max_attempts == Some(0) {
+            return Err(anyhow!("Task policy `max_attempts` must be at least 1"));
+        }
+        Ok(TaskPolicy {
+            pre_condition_timeout: self
+                .pre_condition_timeout
+                .map(secs)
+                .transpose()?
+                .unwrap_or(policy.pre_condition_timeout),
+            pre_condition_poll: self
+                .pre_condition_poll
+                .map(secs)
+                .transpose()?
+                .unwrap_or(policy.pre_condition_poll),
+            timeout: self.timeout.map(secs).transpose()?.or(policy.timeout),
+            max_attempts: self.max_attempts.unwrap_or(policy.max_attempts),
+            backoff,
+            branch_timeout: self
+                .branch_timeout
+                .map(secs)
+                .transpose()?
+                .unwrap_or(policy.branch_timeout),
+            branch_poll: self
+                .branch_poll
+                .map(secs)
+                .transpose()?
+                .unwrap_or(policy.branch_poll),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EdgeDef {
+    pub from: String,
+    pub to: String,
+    /// Edges with a higher priority are tried first
+    #[serde(default)]
+    pub priority: i32,
+    /// Registered guard which must hold for the edge to be taken
+    pub guard: Option<String>,
+}
+
+impl GraphFile {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read graph file {}: {e}", path.display()))?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        toml::from_str(contents).map_err(|e| anyhow!("Failed to parse graph file: {e}"))
+    }
+
+    /// Builds the task graph, returning it alongside the start node
+    pub fn build(&self, registry: &TaskRegistry) -> Result<(DiGraph<Task, TaskEdge>, NodeIndex)> {
+        let mut defs: HashMap<&str, &NodeDef> = HashMap::new();
+        for node in self.nodes.iter() {
+            if defs.insert(node.name.as_str(), node).is_some() {
+                return Err(anyhow!("Node name `{}` is used more than once", node.name));
+            }
+        }
+        for edge in self.edges.iter() {
+            for name in [&edge.from, &edge.to] {
+                if !defs.contains_key(name.as_str()) {
+                    return Err(anyhow!("Edge references unknown node `{name}`"));
+                }
+            }
+        }
+
+        let mut graph: DiGraph<Task, TaskEdge> = DiGraph::new();
+        let mut indices: HashMap<&str, NodeIndex> = HashMap::new();
+        for node in self.nodes.iter().filter(|n| !n.skip) {
+            let task = registry.instantiate(
+                &node.kind,
+                &node.name,
+                &TaskParams::new(node.params.clone()),
+                node.repeatable,
+            )?;
+            let policy = node
+                .policy
+                .apply(task.policy)
+                .map_err(|e| anyhow!("Node `{}`: {e}", node.name))?;
+            let task = task
+                .with_policy(policy)
+                .with_terminal(node.terminal)
+                .with_fork(node.fork)
+                .with_join(node.join);
+            let index = graph.add_node(task);
+            indices.insert(node.name.as_str(), index);
+        }
+
+        for edge in self.edges.iter() {
+            let Some(&source) = indices.get(edge.from.as_str()) else {
+                continue;
+            };
+            let guard = edge
+                .guard
+                .as_deref()
+                .map(|name| registry.guard(name))
+                .transpose()?;
+            for name in self.resolve_targets(&edge.to, &defs) {
+                let target = indices[name];
+                if graph.find_edge(source, target).is_some() {
+                    let bridged = match name == edge.to {
+                        true => "",
+                        false => " through skipped nodes",
+                    };
+                    return Err(anyhow!(
+                        "Duplicate edge `{}` -> `{name}`{bridged}",
+                        edge.from
+                    ));
+                }
+                graph.add_edge(
+                    source,
+                    target,
+                    TaskEdge::outgoing(edge.priority, guard.clone()),
+                );
+            }
+        }
+
+        let start = *indices
+            .get(self.start.as_str())
+            .ok_or_else(|| anyhow!("Start node `{}` is missing or skipped", self.start))?;
+        Ok((graph, start))
+    }
+
+    /// Follows edges through skipped nodes until reaching nodes that are kept
+    fn resolve_targets<'a>(
+        &'a self,
+        name: &'a str,
+        defs: &HashMap<&str, &NodeDef>,
+    ) -> Vec<&'a str> {
+        let mut resolved = Vec::new();
+        let mut pending = vec![name];
+        let mut visited = Vec::new();
+        while let Some(name) = pending.pop() {
+            if visited.contains(&name) {
+                continue;
+            }
+            visited.push(name);
+            if !defs[name].skip {
+                resolved.push(name);
+                continue;
+            }
+            pending.extend(
+                self.edges
+                    .iter()
+                    .filter(|e| e.from == name)
+                    .map(|e| e.to.as_str()),
+            );
+        }
+        resolved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> TaskRegistry {
+        let mut registry = TaskRegistry::default();
+        registry.register("test.noop", |_| async { Ok(()) }, |_| async { Ok(true) });
+        // Names its tasks after their `url` parameter so the tests can see it arrived
+        registry.register_with("test.open", |name, params, repeatable| {
+            let url = params.require("url")?;
+            Ok(Task::new_async(
+                &format!("{name} {url}"),
+                |_| async { Ok(()) },
+                |_| async { Ok(true) },
+                repeatable,
+            ))
+        });
+        registry.register_guard("test.guard", |_| async { Ok(true) });
+        registry
+    }
+
+    fn build(contents: &str) -> Result<(DiGraph<Task, TaskEdge>, NodeIndex)> {
+        GraphFile::parse(contents)?.build(&registry())
+    }
+
+    /// Outgoing edges as `from -> to` pairs, sorted
+    fn edges(graph: &DiGraph<Task, TaskEdge>) -> Vec<String> {
+        let mut edges: Vec<String> = graph
+            .edge_references()
+            .map(|edge| {
+                format!(
+                    "{} -> {}",
+                    graph[edge.source()].name,
+                    graph[edge.target()].name
+                )
+            })
+            .collect();
+        edges.sort();
+        edges
+    }
+
+    #[test]
+    fn loads_nodes_and_edges() {
+        let (graph, start) = build(
+            r#"
+            start = "A"
+
+            [[nodes]]
+            name = "A"
+            kind = "test.open"
+            params = { url = "https://wa.test" }
+
+            [[nodes]]
+            name = "B"
+            kind = "test.noop"
+            repeatable = true
+            terminal = true
+
+            [[edges]]
+            from = "A"
+            to = "B"
+            priority = 5
+            guard = "test.guard"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(graph[start].name, "A https://wa.test");
+        assert_eq!(edges(&graph), vec!["A https://wa.test -> B"]);
+        let edge = graph.edge_references().next().unwrap().weight();
+        assert_eq!(edge.priority, 5);
+        assert_eq!(edge.guard.as_ref().unwrap().name, "test.guard");
+        let b = graph.edge_references().next().unwrap().target();
+        assert!(graph[b].repeatable && graph[b].terminal);
+        assert_eq!(graph[b].policy.timeout, None);
+    }
+
+    #[test]
+    fn bridges_skipped_nodes() {
+        let (graph, _) = build(
+            r#"
+            start = "A"
+            nodes = [
+                { name = "A", kind = "test.noop" },
+                { name = "Skipped", kind = "test.noop", skip = true },
+                { name = "AlsoSkipped", kind = "test.noop", skip = true },
+                { name = "B", kind = "test.noop" },
+                { name = "C", kind = "test.noop", terminal = true },
+            ]
+            edges = [
+                { from = "A", to = "Skipped" },
+                { from = "Skipped", to = "AlsoSkipped" },
+                { from = "AlsoSkipped", to = "B" },
+                { from = "Skipped", to = "C" },
+                { from = "B", to = "C" },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(edges(&graph), vec!["A -> B", "A -> C", "B -> C"]);
+    }
+
+    #[test]
+    fn applies_policy_overrides() {
+        let (graph, start) = build(
+            r#"
+            start = "A"
+
+            [[nodes]]
+            name = "A"
+            kind = "test.noop"
+            terminal = true
+            policy = { timeout = 1.5, max_attempts = 3, branch_poll = 0.05, backoff = { kind = "exponential", initial = 1, max = 8 } }
+            "#,
+        )
+        .unwrap();
+        let policy = graph[start].policy;
+        assert_eq!(policy.timeout, Some(Duration::from_millis(1500)));
+        assert_eq!(policy.max_attempts, 3);
+        assert_eq!(policy.branch_poll, Duration::from_millis(50));
+        assert_eq!(
+            policy.backoff,
+            Backoff::Exponential {
+                initial: Duration::from_secs(1),
+                max: Duration::from_secs(8),
+            }
+        );
+        assert_eq!(
+            policy.pre_condition_timeout,
+            TaskPolicy::default().pre_condition_timeout
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_graphs() {
+        let error = |contents: &str| build(contents).err().unwrap().to_string();
+        let nodes = r#"
+            [[nodes]]
+            name = "A"
+            kind = "test.noop"
+
+            [[nodes]]
+            name = "B"
+            kind = "test.noop"
+            terminal = true
+        "#;
+
+        assert_eq!(
+            error(&format!(
+                "start = \"A\"\n{nodes}\n[[nodes]]\nname = \"A\"\nkind = \"test.noop\""
+            )),
+            "Node name `A` is used more than once"
+        );
+        assert_eq!(
+            error(&format!(
+                "start = \"A\"\nedges = [{{ from = \"A\", to = \"B\" }}, {{ from = \"A\", to = \"B\", priority = 1 }}]\n{nodes}"
+            )),
+            "Duplicate edge `A` -> `B`"
+        );
+        assert_eq!(
+            error(&format!(
+                "start = \"A\"\nedges = [{{ from = \"A\", to = \"C\" }}]\n{nodes}"
+            )),
+            "Edge references unknown node `C`"
+        );
+        assert_eq!(
+            error(&format!(
+                "start = \"A\"\nedges = [{{ from = \"A\", to = \"B\", guard = \"test.missing\" }}]\n{nodes}"
+            )),
+            "Unknown guard `test.missing`"
+        );
+        assert_eq!(
+            error(&format!("start = \"C\"\n{nodes}")),
+            "Start node `C` is missing or skipped"
+        );
+        assert_eq!(
+            error("start = \"A\"\nnodes = [{ name = \"A\", kind = \"test.open\" }]"),
+            "Failed to create task `A` of kind `test.open`: Missing required task parameter `url`"
+        );
+        assert_eq!(
+            error("start = \"A\"\nnodes = [{ name = \"A\", kind = \"test.noop\", policy = { max_attempts = 0 } }]"),
+            "Node `A`: Task policy `max_attempts` must be at least 1"
+        );
+        assert!(error(
+            "start = \"A\"\nnodes = [{ name = \"A\", kind = \"test.noop\", policy = { retries = 2 } }]"
+        )
+        .starts_with("Failed to parse graph file"));
+    }
+
+    #[test]
+    fn rejects_duplicate_edges_through_skipped_nodes() {
+        let error = build(
+            r#"
+            start = "A"
+            nodes = [
+                { name = "A", kind = "test.noop" },
+                { name = "Skipped", kind = "test.noop", skip = true },
+                { name = "B", kind = "test.noop", terminal = true },
+            ]
+            edges = [
+                { from = "A", to = "B" },
+                { from = "A", to = "Skipped" },
+                { from = "Skipped", to = "B" },
+            ]
+            "#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Duplicate edge `A` -> `B` through skipped nodes"
+        );
+    }
+
+    #[test]
+    fn loads_the_repository_graph() {
+        let mut registry = TaskRegistry::default();
+        crate::browser::register(&mut registry);
+        crate::microsoft::register(&mut registry);
+        crate::wa::register(&mut registry);
+        let (mut graph, start) = GraphFile::load("graph.toml")
+            .unwrap()
+            .build(&registry)
+            .unwrap();
+        crate::ensure_bidirectional_edges(&mut graph);
+        assert_eq!(crate::validate::validate(&graph, start), vec![]);
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+
+/// Header names whose values are never written to the HAR file
+const REDACTED_HEADERS: [&str; 6] = [
+    "authorization",
+    "cookie",
+    "set-cookie",
+    "x-csrf-token",
+    "__requestverificationtoken",
+    "requestverificationtoken",
+];
+
+/// Form fields holding credentials, redacted from request bodies
+const REDACTED_FIELDS: [&str; 4] = ["passwd", "password", "otc", "__requestverificationtoken"];
+
+pub struct RecordedRequest {
+    pub id: String,
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub post_data: Option<String>,
+    pub started: DateTime<Utc>,
+    /// Monotonic seconds, only compared with the timestamp of the response
+    pub timestamp: f64,
+}
+
+pub struct RecordedResponse {
+    pub id: String,
+    pub status: u32,
+    pub status_text: String,
+    pub headers: Vec<(String, String)>,
+    pub mime_type: String,
+    /// Only kept for JSON responses to XHR and fetch requests
+    pub body: Option<String>,
+    pub timestamp: f64,
+}
+
+#[derive(Default)]
+struct HarLog {
+    pending: HashMap<String, RecordedRequest>,
+    entries: Vec<Value>,
+    /// Whether entries were added since the last save
+    dirty: bool,
+}
+
+/// Records the network traffic of the pages it is attached to as HAR 1.2 entries
+#[derive(Clone, Default)]
+pub struct HarRecorder {
+    log: Arc<Mutex<HarLog>>,
+}
+
+fn redact_headers(headers: &[(String, String)]) -> Value {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = match REDACTED_HEADERS.contains(&name.to_lowercase().as_str()) {
+                true => "[redacted]",
+                false => value.as_str(),
+            };
+            json!({ "name": name, "value": value })
+        })
+        .collect()
+}
+
+fn is_redacted_field(name: &str) -> bool {
+    REDACTED_FIELDS.contains(&name.to_lowercase().as_str())
+}
+
+fn redact_form(body: &str) -> String {
+    let fields: Vec<(String, String)> = form_urlencoded::parse(body.as_bytes())
+        .into_owned()
+        .collect();
+    if !fields.iter().any(|(name, _)| is_redacted_field(name)) {
+        return body.to_string();
+    }
+    let mut redacted = form_urlencoded::Serializer::new(String::new());
+    for (name, value) in fields.iter() {
+        match is_redacted_field(name) {
+            true => redacted.append_pair(name, "[redacted]"),
+            false => redacted.append_pair(name, value),
+        };
+    }
+    redacted.finish()
+}
+
+fn query_string(url: &str) -> Value {
+    let query = url.split_once('?').map(|(_, query)| query).unwrap_or("");
+    form_urlencoded::parse(query.as_bytes())
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect()
+}
+
+impl HarRecorder {
+    pub fn request(&self, request: RecordedRequest) {
+        let mut log = self.log.lock().unwrap();
+        log.pending.insert(request.id.clone(), request);
+    }
+
+    /// Completes the entry of the matching request, responses to unknown requests are ignored
+    pub fn response(&self, response: RecordedResponse) {
+        let mut log = self.log.lock().unwrap();
+        let Some(request) = log.pending.remove(&response.id) else {
+            return;
+        };
+        let time = ((response.timestamp - request.timestamp) * 1000.0).max(0.0);
+        let content_type = request
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default();
+        let post_data = request.post_data.as_ref().map(|body| {
+            json!({
+                "mimeType": content_type,
+                "text": redact_form(body),
+            })
+        });
+        let mut content = json!({
+            "size": response.body.as_ref().map_or(-1, |body| body.len() as i64),
+            "mimeType": response.mime_type,
+        });
+        if let Some(body) = &response.body {
+            content["text"] = json!(body);
+        }
+        let mut entry = json!({
+            "startedDateTime": request.started.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "time": time,
+            "request": {
+                "method": request.method,
+                "url": request.url,
+                "httpVersion": "HTTP/1.1",
+                "cookies": [],
+                "headers": redact_headers(&request.headers),
+                "queryString": query_string(&request.url),
+                "headersSize": -1,
+                "bodySize": request.post_data.as_ref().map_or(0, |body| body.len() as i64),
+            },
+            "response": {
+                "status": response.status,
+                "statusText": response.status_text,
+                "httpVersion": "HTTP/1.1",
+                "cookies": [],
+                "headers": redact_headers(&response.headers),
+                "content": content,
+                "redirectURL": "",
+                "headersSize": -1,
+                "bodySize": -1,
+            },
+            "cache": {},
+            "timings": { "send": 0, "wait": time, "receive": 0 },
+        });
+        if let Some(post_data) = post_data {
+            entry["request"]["postData"] = post_data;
+        }
+        log.entries.push(entry);
+        log.dirty = true;
+    }
+
+    /// Writes the HAR file if anything was recorded since the last save
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut log = self.log.lock().unwrap();
+        if !log.dirty {
+            return Ok(());
+        }
+        fs::write(path, serde_json::to_string_pretty(&to_json(&log))?)?;
+        log.dirty = false;
+        Ok(())
+    }
+}
+
+fn to_json(log: &HarLog) -> Value {
+    json!({
+        "log": {
+            "version": "1.2",
+            "creator": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "entries": log.entries,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    fn request(id: &str, post_data: Option<&str>) -> RecordedRequest {
+        RecordedRequest {
+            id: id.to_string(),
+            method: "POST".to_string(),
+            url: "https://wa.test/Student/Planning/DegreePlans/RegisterSections?term=F25"
+                .to_string(),
+            headers: vec![
+                header("Content-Type", "application/x-www-form-urlencoded"),
+                header("Cookie", "session=secret"),
+            ],
+            post_data: post_data.map(|body| body.to_string()),
+            started: Utc::now(),
+            timestamp: 10.0,
+        }
+    }
+
+    fn response(id: &str) -> RecordedResponse {
+        RecordedResponse {
+            id: id.to_string(),
+            status: 200,
+            status_text: "OK".to_string(),
+            headers: vec![header("Set-Cookie", "session=secret")],
+            mime_type: "application/json".to_string(),
+            body: Some(r#"{"Messages":[]}"#.to_string()),
+            timestamp: 10.25,
+        }
+    }
+
+    fn entries(recorder: &HarRecorder) -> Vec<Value> {
+        recorder.log.lock().unwrap().entries.clone()
+    }
+
+    #[test]
+    fn redacts_headers_whatever_their_case() {
+        let headers = vec![
+            header("Authorization", "Bearer secret"),
+            header("X-CSRF-Token", "secret"),
+            header("__RequestVerificationToken", "secret"),
+            header("Accept", "application/json"),
+        ];
+        assert_eq!(
+            redact_headers(&headers),
+            json!([
+                { "name": "Authorization", "value": "[redacted]" },
+                { "name": "X-CSRF-Token", "value": "[redacted]" },
+                { "name": "__RequestVerificationToken", "value": "[redacted]" },
+                { "name": "Accept", "value": "application/json" },
+            ])
+        );
+    }
+
+    #[test]
+    fn redacts_form_fields_whatever_their_case() {
+        assert_eq!(
+            redact_form("login=student&Passwd=secret&__RequestVerificationToken=token"),
+            "login=student&Passwd=%5Bredacted%5D&__RequestVerificationToken=%5Bredacted%5D"
+        );
+        assert_eq!(redact_form("OTC=123456"), "OTC=%5Bredacted%5D");
+        // Bodies without credentials are kept as they were sent
+        let json = r#"{"sectionIds":["CIS*2500*0101"]}"#;
+        assert_eq!(redact_form(json), json);
+    }
+
+    #[test]
+    fn builds_an_entry_per_answered_request() {
+        let recorder = HarRecorder::default();
+        recorder.request(request("1", Some("password=secret&term=F25")));
+        recorder.request(request("2", None));
+        recorder.response(response("1"));
+        recorder.response(response("unknown"));
+
+        let entries = entries(&recorder);
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry["time"], json!(250.0));
+        assert_eq!(entry["request"]["method"], "POST");
+        assert_eq!(
+            entry["request"]["queryString"],
+            json!([{ "name": "term", "value": "F25" }])
+        );
+        assert_eq!(entry["request"]["headers"][1]["value"], "[redacted]");
+        assert_eq!(
+            entry["request"]["postData"],
+            json!({
+                "mimeType": "application/x-www-form-urlencoded",
+                "text": "password=%5Bredacted%5D&term=F25",
+            })
+        );
+        assert_eq!(entry["request"]["bodySize"], 24);
+        assert_eq!(entry["response"]["status"], 200);
+        assert_eq!(entry["response"]["headers"][0]["value"], "[redacted]");
+        assert_eq!(
+            entry["response"]["content"],
+            json!({ "size": 15, "mimeType": "application/json", "text": r#"{"Messages":[]}"# })
+        );
+    }
+
+    #[test]
+    fn saves_only_when_something_was_recorded() {
+        let path = std::env::temp_dir().join(format!("har-{}.har", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let recorder = HarRecorder::default();
+        recorder.save(&path).unwrap();
+        assert!(!path.exists());
+
+        recorder.request(request("1", None));
+        recorder.response(response("1"));
+        recorder.save(&path).unwrap();
+        let saved: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["log"]["version"], "1.2");
+        assert_eq!(saved["log"]["entries"].as_array().unwrap().len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,38 +1,86 @@
 extern crate core;
 
-use std::collections::{VecDeque};
 use std::future::Future;
-use std::sync::{Arc, RwLock};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::{anyhow, Result};
-use headless_chrome::{Browser, Tab};
-use petgraph::data::Build;
+use anyhow::Result;
 use petgraph::prelude::*;
 use tokio::time;
-use tokio::time::sleep;
-
-use crate::task::Task;
 
+use crate::artifacts::Artifacts;
+use crate::clock::ServerClock;
+use crate::context::Context;
+use crate::cookie_jar::CookieJar;
+use crate::driver::chrome::ChromeConfig;
+use crate::executor::{BranchEnd, Executor};
+use crate::har::HarRecorder;
+use crate::report::RunReport;
+use crate::schedule::Schedule;
+use crate::task::{Guard, Task, TaskRegistry};
+
+mod artifacts;
+mod branch;
 mod browser;
+mod checkpoint;
+mod cli;
+mod clock;
+mod colleague;
+mod context;
+mod cookie_jar;
+mod driver;
+mod executor;
+mod export;
+mod graph;
+mod har;
 mod microsoft;
+mod rate;
+mod report;
+mod schedule;
+mod standin;
 pub mod task;
+mod validate;
 pub mod wa;
 
-pub struct ContextInner {
-    pub browser: Browser,
-    pub tab: Arc<Tab>,
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum EdgeKind {
+    Outgoing,
+    Incoming,
 }
 
-#[derive(Clone, Default)]
-pub struct Context {
-    pub inner: Arc<RwLock<Option<ContextInner>>>,
+#[derive(Clone)]
+pub struct TaskEdge {
+    pub kind: EdgeKind,
+    /// Outgoing edges with a higher priority are considered first
+    pub priority: i32,
+    /// Extra condition which must hold for the edge to be taken
+    pub guard: Option<Guard>,
 }
 
-#[derive(Copy, Clone, PartialEq)]
-pub enum TaskEdge {
-    Outgoing,
-    Incoming,
+impl TaskEdge {
+    pub fn outgoing(priority: i32, guard: Option<Guard>) -> Self {
+        TaskEdge {
+            kind: EdgeKind::Outgoing,
+            priority,
+            guard,
+        }
+    }
+
+    pub fn is_outgoing(&self) -> bool {
+        self.kind == EdgeKind::Outgoing
+    }
+
+    /// The same edge seen from its target
+    pub fn reversed(&self) -> Self {
+        TaskEdge {
+            kind: match self.kind {
+                EdgeKind::Outgoing => EdgeKind::Incoming,
+                EdgeKind::Incoming => EdgeKind::Outgoing,
+            },
+            ..self.clone()
+        }
+    }
 }
 
 fn ensure_bidirectional_edges(graph: &mut DiGraph<Task, TaskEdge>) {
@@ -40,12 +88,7 @@ fn ensure_bidirectional_edges(graph: &mut DiGraph<Task, TaskEdge>) {
     for edge in graph.edge_references() {
         let (source, target) = (edge.source(), edge.target());
         if graph.find_edge(target, source).is_none() {
-            let reverse_edge_type = match edge.weight() {
-                TaskEdge::Outgoing => TaskEdge::Incoming,
-                TaskEdge::Incoming => TaskEdge::Outgoing,
-            };
-
-            edges_to_add.push((target, source, reverse_edge_type));
+            edges_to_add.push((target, source, edge.weight().reversed()));
         }
     }
     for (source, target, edge_type) in edges_to_add {
@@ -63,169 +106,140 @@ where
     }
 }
 
-async fn execute_task(graph: &DiGraph<Task, TaskEdge>, ctx: Context, node: NodeIndex) -> bool {
-    let task = &graph[node];
-
-    let pre_condition_timeout = Duration::from_secs(5);
-    let task_timeout = Duration::from_secs(20);
-
-    // Retry precondition until timeout
-    let pre_condition_future = async {
-        let start = time::Instant::now();
-        loop {
-            if start.elapsed() >= pre_condition_timeout {
-                return Err(anyhow!("Precondition check timed out"));
-            }
-
-            match (task.pre_condition)(ctx.clone()).await {
-                Ok(true) => return Ok(true),
-                Ok(false) => {
-                    tokio::time::sleep(Duration::from_millis(100)).await;
-                    continue;
-                }
-                Err(e) => return Err(e),
-            }
+fn save_har(har: &Option<(HarRecorder, PathBuf)>) {
+    if let Some((recorder, path)) = har {
+        if let Err(e) = recorder.save(path) {
+            eprintln!("Failed to save HAR file: {e}");
         }
-    };
+    }
+}
 
-    match run_with_timeout(pre_condition_future, pre_condition_timeout).await {
-        Ok(true) => {
-            let task_future = (task.func)(ctx);
-            if task.repeatable {
-                task_future
-                    .await
-                    .map_err(|e| {
-                        eprintln!("Failed task {}, {e}", task.name);
-                        e
-                    })
-                    .is_ok()
-            } else {
-                match run_with_timeout(task_future, task_timeout).await {
-                    Ok(_) => true,
-                    Err(e) => {
-                        println!(
-                            "Task function failed for node {:?}: {:?}",
-                            &graph[node].name, e
-                        );
-                        false
-                    }
-                }
-            }
-        }
-        Ok(false) => {
-            println!("Precondition not met for node {:?}", &graph[node].name);
-            false
-        }
-        Err(e) => {
-            println!(
-                "Precondition check timed out or failed for node {:?}: {:?}",
-                &graph[node].name, e
-            );
-            false
+/// Resolves once the process is asked to stop, by Ctrl-C or on Unix also by SIGTERM
+async fn shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
         }
     }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+    Ok(())
 }
+
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().unwrap();
-    let mut task_graph: Graph<Task, TaskEdge> = DiGraph::new();
-    let browser_create = task_graph.add_node(Task::new_async(
-        "Create browser",
-        browser::open_browser_function,
-        browser::open_browser_precondition,
-        false,
-    ));
-    let navigate_wa = task_graph.add_node(Task::new_async(
-        "Navigate to WA",
-        browser::navigate_to_webadvisor_function,
-        browser::navigate_to_webadvisor_precondition,
-        false,
-    ));
-
-    let ms_email = task_graph.add_node(Task::new_async(
-        "Login ms email",
-        microsoft::login_microsoft_email_function,
-        microsoft::login_microsoft_email_precondition,
-        false,
-    ));
-    let ms_password = task_graph.add_node(Task::new_async(
-        "Login ms password",
-        microsoft::login_microsoft_password_function,
-        microsoft::login_microsoft_password_precondition,
-        false,
-    ));
-    let ms_otp = task_graph.add_node(Task::new_async(
-        "Login ms otp",
-        microsoft::login_microsoft_otp_function,
-        microsoft::login_microsoft_otp_precondition,
-        false,
-    ));
-    let wa_navigate = task_graph.add_node(Task::new_async(
-        "Navigate WA",
-        wa::wa_navigate_semester_function,
-        wa::wa_navigate_semester_precondition,
-        false,
-    ));
-    let wa_button = task_graph.add_node(Task::new_async(
-        "Button WA",
-        wa::wa_register_function,
-        wa::wa_register_precondition,
-        true,
-    ));
-
-    task_graph.add_edge(browser_create, navigate_wa, TaskEdge::Outgoing);
-    task_graph.add_edge(navigate_wa, ms_email, TaskEdge::Outgoing);
-    task_graph.add_edge(ms_email, ms_password, TaskEdge::Outgoing);
-    task_graph.add_edge(ms_password, ms_otp, TaskEdge::Outgoing);
-    task_graph.add_edge(ms_otp, wa_navigate, TaskEdge::Outgoing);
-    task_graph.add_edge(wa_navigate, wa_button, TaskEdge::Outgoing);
+    let args = cli::Args::parse().unwrap();
+    if args.standin {
+        let microsoft = standin::microsoft::MicrosoftStandin::from_env().unwrap();
+        let sessions = microsoft.sessions();
+        let sign_in_url = standin::serve(Arc::new(microsoft)).await.unwrap();
+        println!("Serving the Microsoft sign-in stand-in at {sign_in_url}");
+        let webadvisor = standin::webadvisor::WebAdvisorStandin::from_env()
+            .unwrap()
+            .with_sign_in(&format!("{sign_in_url}/login"), sessions);
+        let url = standin::serve(Arc::new(webadvisor)).await.unwrap();
+        println!("Serving the WebAdvisor stand-in at {url}");
+        std::env::set_var("WA_BASE_URL", url);
+    }
+    let mut registry = TaskRegistry::default();
+    browser::register(&mut registry);
+    microsoft::register(&mut registry);
+    wa::register(&mut registry);
+    let graph_path = dotenv::var("TASK_GRAPH").unwrap_or_else(|_| "graph.toml".to_string());
+    let (mut task_graph, start) = graph::GraphFile::load(&graph_path)
+        .and_then(|file| file.build(&registry))
+        .unwrap();
     ensure_bidirectional_edges(&mut task_graph);
+    let issues = validate::validate(&task_graph, start);
+    if !issues.is_empty() {
+        for issue in issues.iter() {
+            eprintln!("Invalid task graph: {issue}");
+        }
+        std::process::exit(1);
+    }
+    let report_path = dotenv::var("LAST_RUN").unwrap_or_else(|_| "last_run.toml".to_string());
+    if let Some(format) = args.print_graph {
+        let report = match args.with_last_run {
+            true => Some(RunReport::load(&report_path).unwrap()),
+            false => None,
+        };
+        print!("{}", export::render(&task_graph, format, report.as_ref()));
+        return;
+    }
+    let executor = Executor::new(task_graph, report_path);
     let ctx = Context::default();
-    loop {
-        let mut stack: VecDeque<NodeIndex> = VecDeque::from(vec![browser_create]);
-        while let Some(node) = stack.pop_front() {
-            println!("{:?}", node);
-            match execute_task(&task_graph, ctx.clone(), node).await {
-                true => {}
-                false => {
-                    break;
-                }
-            };
-            match run_with_timeout(
-                {
-                    let task_graph = &task_graph;
-                    let ctx = ctx.clone();
-                    async move {
-                        loop {
-                            for edge in task_graph.edges(node) {
-                                if *edge.weight() != TaskEdge::Outgoing {
-                                    continue;
-                                }
-                                let node = edge.target();
-                                let task = &task_graph[node];
-                                if (task.pre_condition)(ctx.clone()).await.ok().is_some() {
-                                    return Ok(node);
-                                } else {
-                                    println!("Failed {}", &task_graph[node].name);
-                                }
-                            }
-                            sleep(Duration::from_millis(10)).await;
-                        }
-                    }
-                },
-                Duration::from_secs(20),
-            )
-            .await
-            {
-                Ok(node) => {
-                    println!("Success!");
-                    stack.push_back(node);
+    let mut chrome = ChromeConfig::from_env().unwrap();
+    if let Some(headless) = args.headless {
+        chrome.headless = headless;
+    }
+    if let Some(url) = args.attach.clone() {
+        chrome.remote_url = Some(url);
+    }
+    ctx.insert(chrome).unwrap();
+    let artifacts = Artifacts::from_env();
+    ctx.insert(artifacts.clone()).unwrap();
+    let mut har = None;
+    if dotenv::var("RECORD_HAR").is_ok_and(|record| record == "true") {
+        let recorder = HarRecorder::default();
+        ctx.insert(recorder.clone()).unwrap();
+        har = Some((recorder, artifacts.file("network.har").unwrap()));
+        // The run usually ends by being interrupted, so keep the file up to date as it goes
+        let periodic = har.clone();
+        tokio::spawn(async move {
+            loop {
+                time::sleep(Duration::from_secs(5)).await;
+                save_har(&periodic);
+            }
+        });
+        // And save what came in since the last periodic save when it is
+        let interrupted = har.clone();
+        tokio::spawn(async move {
+            match shutdown_signal().await {
+                Ok(()) => {
+                    save_har(&interrupted);
+                    std::process::exit(130);
                 }
-                Err(e) => {
-                    eprintln!("Timed out! {e}");
-                    break;
+                Err(e) => eprintln!("Not saving the HAR file on shutdown: {e}"),
+            }
+        });
+    }
+    if let Some(jar) = CookieJar::from_env().unwrap() {
+        ctx.insert(jar).unwrap();
+    }
+    if let Some(schedule) = Schedule::from_env().unwrap() {
+        ctx.insert(schedule).unwrap();
+    }
+    let clock = ServerClock::default();
+    ctx.insert(clock.clone()).unwrap();
+    if !dotenv::var("SYNC_SERVER_CLOCK").is_ok_and(|sync| sync == "false") {
+        let url = browser::webadvisor_base_url();
+        // Clocks drift apart over the hours before the window opens, so keep measuring
+        tokio::spawn(async move {
+            loop {
+                match clock.sample(&url, 5).await {
+                    Ok(()) => println!(
+                        "Server clock is off by {}ms",
+                        clock.offset().num_milliseconds()
+                    ),
+                    Err(e) => eprintln!("Failed to measure the server clock: {e}"),
                 }
+                time::sleep(Duration::from_secs(600)).await;
             }
+        });
+    }
+    loop {
+        let resume =
+            checkpoint::resume_node(executor.graph(), start, &ctx, &executor.checkpoint()).await;
+        let end = executor.clone().run(ctx.clone(), resume).await;
+        // The last requests, usually the registration, came in after the last periodic save
+        save_har(&har);
+        if end == BranchEnd::Terminal {
+            return;
         }
     }
 }
@@ -1,115 +1,200 @@
 use std::env::var;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, Result};
-use chrono::Utc;
 use otpauth::TOTP;
 
-use crate::Context;
+use crate::clock::ServerClock;
+use crate::context::Context;
+use crate::task::TaskRegistry;
 
 pub async fn login_microsoft_email_precondition(context: Context) -> Result<bool> {
-    let guard = context
-        .inner
-        .read()
-        .map_err(|_| anyhow!("Poison error"))?;
-    if guard.is_none() {
-        return Err(anyhow!("Expected browser + tab, found `None`."));
-    }
-    let tab = guard.as_ref().unwrap().tab.clone();
-    drop(guard);
-    tab.wait_until_navigated()?;
-    tab.find_element("input[type='email']").map(|_| Ok(true))?
+    let page = context.page()?;
+    page.wait_until_navigated()?;
+    page.find_element("input[type='email']").map(|_| true)
 }
 
 pub async fn login_microsoft_email_function(context: Context) -> Result<()> {
-    let guard = context
-        .inner
-        .read()
-        .map_err(|_| anyhow!("Poison error"))?;
-    if guard.is_none() {
-        return Err(anyhow!("Expected browser + tab, found `None`."));
-    }
-    let tab = guard.as_ref().unwrap().tab.clone();
-    drop(guard);
-    let email_input = tab.find_element("input[type='email']")?;
-    email_input.type_into(var("EMAIL")?.as_str())?;
-    email_input.parent.press_key("Enter")?;
-    Ok(())
+    let page = context.page()?;
+    page.type_into("input[type='email']", var("EMAIL")?.as_str())?;
+    page.press_key("Enter")
 }
 
 pub async fn login_microsoft_password_precondition(context: Context) -> Result<bool> {
-    let guard = context
-        .inner
-        .read()
-        .map_err(|_| anyhow!("Poison error"))?;
-    if guard.is_none() {
-        return Err(anyhow!("Expected browser + tab, found `None`."));
-    }
-    let tab = guard.as_ref().unwrap().tab.clone();
-    drop(guard);
-    tab.wait_until_navigated()?;
-    tab.find_element("input[type='password']")
-        .map(|_| Ok(true))?
+    let page = context.page()?;
+    page.wait_until_navigated()?;
+    page.find_element("input[type='password']").map(|_| true)
 }
 
 pub async fn login_microsoft_password_function(context: Context) -> Result<()> {
-    let guard = context
-        .inner
-        .read()
-        .map_err(|_| anyhow!("Poison error"))?;
-    if guard.is_none() {
-        return Err(anyhow!("Expected browser + tab, found `None`."));
-    }
-    let tab = guard.as_ref().unwrap().tab.clone();
-    drop(guard);
-    let password_input = tab.find_element("input[type='password']")?;
-    password_input.type_into(var("PASSWORD")?.as_str())?;
-    password_input.parent.press_key("Enter")?;
-    Ok(())
+    let page = context.page()?;
+    page.type_into("input[type='password']", var("PASSWORD")?.as_str())?;
+    page.press_key("Enter")
 }
 
-pub async fn acquire_2fa_code() -> Result<String> {
-    let totp = TOTP::new::<String>(var("OTP").unwrap().as_str().parse()?)
-        .generate(30, Utc::now().timestamp() as u64);
-    Ok(totp.to_string())
+/// Current TOTP code, generated against the server's `clock` rather than the local one
+pub async fn acquire_2fa_code(clock: &ServerClock) -> Result<String> {
+    let totp = TOTP::from_base32(var("OTP")?.as_str())
+        .ok_or_else(|| anyhow!("`OTP` is not a valid base32 secret"))?;
+    Ok(format!(
+        "{:06}",
+        totp.generate(30, clock.now().timestamp() as u64)
+    ))
 }
 
 pub async fn login_microsoft_otp_precondition(context: Context) -> Result<bool> {
-    let guard = context
-        .inner
-        .read()
-        .map_err(|_| anyhow!("Poison error"))?;
-    if guard.is_none() {
-        return Err(anyhow!("Expected browser + tab, found `None`."));
-    }
-    let tab = guard.as_ref().unwrap().tab.clone();
-    drop(guard);
-    tab.wait_until_navigated()?;
-    tab.find_element("#idTxtBx_SAOTCC_OTC").map(|_| Ok(true))?
+    let page = context.page()?;
+    page.wait_until_navigated()?;
+    page.find_element("#idTxtBx_SAOTCC_OTC").map(|_| true)
 }
 
 pub async fn login_microsoft_otp_function(context: Context) -> Result<()> {
-    let guard = context
-        .inner
-        .read()
-        .map_err(|_| anyhow!("Poison error"))?;
-    if guard.is_none() {
-        return Err(anyhow!("Expected browser + tab, found `None`."));
+    let page = context.page()?;
+    page.wait_until_navigated()?;
+    page.find_element("#idTxtBx_SAOTCC_OTC")?;
+    let clock = context.get::<ServerClock>()?.unwrap_or_default();
+    let code = acquire_2fa_code(&clock).await?;
+    page.type_into("#idTxtBx_SAOTCC_OTC", code.as_str())?;
+    page.press_key("Enter")
+}
+
+pub async fn stay_signed_in_precondition(context: Context) -> Result<bool> {
+    let page = context.page()?;
+    page.wait_until_navigated()?;
+    page.find_element("#KmsiCheckboxField").map(|_| true)
+}
+
+/// Answers "Stay signed in?" with yes so the session survives browser restarts
+pub async fn stay_signed_in_function(context: Context) -> Result<()> {
+    let page = context.page()?;
+    page.click("#idSIButton9")
+}
+
+/// Whether the page asks for a one-time code, `false` rather than an error when it doesn't
+pub async fn mfa_prompt_guard(context: Context) -> Result<bool> {
+    let page = context.page()?;
+    page.wait_until_navigated()?;
+    Ok(page.find_element("#idTxtBx_SAOTCC_OTC").is_ok())
+}
+
+/// Whether the page asks "Stay signed in?", `false` rather than an error when it doesn't
+pub async fn stay_signed_in_prompt_guard(context: Context) -> Result<bool> {
+    let page = context.page()?;
+    page.wait_until_navigated()?;
+    Ok(page.find_element("#KmsiCheckboxField").is_ok())
+}
+
+pub fn register(registry: &mut TaskRegistry) {
+    registry.register(
+        "microsoft.email",
+        login_microsoft_email_function,
+        login_microsoft_email_precondition,
+    );
+    registry.register(
+        "microsoft.password",
+        login_microsoft_password_function,
+        login_microsoft_password_precondition,
+    );
+    registry.register(
+        "microsoft.otp",
+        login_microsoft_otp_function,
+        login_microsoft_otp_precondition,
+    );
+    registry.register(
+        "microsoft.stay_signed_in",
+        stay_signed_in_function,
+        stay_signed_in_precondition,
+    );
+    registry.register_guard("microsoft.mfa_prompt", mfa_prompt_guard);
+    registry.register_guard(
+        "microsoft.stay_signed_in_prompt",
+        stay_signed_in_prompt_guard,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::mock::{mock_context, MockScript};
+
+    const PLAN_URL: &str = "https://wa.test/Student/Planning/DegreePlans";
+
+    fn signed_out() -> Context {
+        let terms = vec!["Fall 2025".to_string()];
+        mock_context(MockScript::webadvisor(PLAN_URL, &terms), PLAN_URL, false)
+    }
+
+    #[tokio::test]
+    async fn signs_in_through_every_step() {
+        let context = signed_out();
+        assert!(login_microsoft_email_precondition(context.clone())
+            .await
+            .unwrap());
+        login_microsoft_email_function(context.clone())
+            .await
+            .unwrap();
+        assert!(login_microsoft_password_precondition(context.clone())
+            .await
+            .unwrap());
+        login_microsoft_password_function(context.clone())
+            .await
+            .unwrap();
+        assert!(login_microsoft_otp_precondition(context.clone())
+            .await
+            .unwrap());
+        login_microsoft_otp_function(context.clone()).await.unwrap();
+        assert!(stay_signed_in_precondition(context.clone()).await.unwrap());
+        stay_signed_in_function(context.clone()).await.unwrap();
+
+        let page = context.page().unwrap();
+        assert!(page.find_element("#register-button").is_ok());
+        assert!(!page.cookies().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn otp_codes_keep_their_leading_zeros() {
+        crate::driver::mock::test_env();
+        // Codes of the next few hours, of which about one in ten starts with a zero
+        for step in 0..500 {
+            let clock = ServerClock::default();
+            let now = chrono::Utc::now();
+            let date = now + chrono::TimeDelta::seconds(step * 30);
+            clock.observe(&date.to_rfc2822(), now, now).unwrap();
+            let code = acquire_2fa_code(&clock).await.unwrap();
+            assert_eq!(code.len(), 6, "{code}");
+            assert!(code.bytes().all(|digit| digit.is_ascii_digit()), "{code}");
+        }
+    }
+
+    #[tokio::test]
+    async fn steps_wait_for_their_page() {
+        let context = signed_out();
+        assert!(login_microsoft_password_precondition(context.clone())
+            .await
+            .is_err());
+        assert!(login_microsoft_otp_precondition(context.clone())
+            .await
+            .is_err());
+        assert!(login_microsoft_otp_function(context.clone()).await.is_err());
+        assert!(stay_signed_in_precondition(context).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn guards_are_false_away_from_their_prompt() {
+        let context = signed_out();
+        assert!(!mfa_prompt_guard(context.clone()).await.unwrap());
+        assert!(!stay_signed_in_prompt_guard(context.clone()).await.unwrap());
+
+        login_microsoft_email_function(context.clone())
+            .await
+            .unwrap();
+        login_microsoft_password_function(context.clone())
+            .await
+            .unwrap();
+        assert!(mfa_prompt_guard(context.clone()).await.unwrap());
+        assert!(!stay_signed_in_prompt_guard(context.clone()).await.unwrap());
+
+        login_microsoft_otp_function(context.clone()).await.unwrap();
+        assert!(!mfa_prompt_guard(context.clone()).await.unwrap());
+        assert!(stay_signed_in_prompt_guard(context).await.unwrap());
     }
-    let tab = guard.as_ref().unwrap().tab.clone();
-    drop(guard);
-    tab.wait_until_navigated()?;
-    let element = tab.find_element("#idTxtBx_SAOTCC_OTC")?;
-    let code: String = {
-        let totp = TOTP::from_base32(var("OTP")?.as_str()).expect("Failed to create TOTP instance");
-        let current_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        totp.generate(30, current_time).to_string()
-    };
-    element.type_into(code.as_str())?;
-    element.parent.press_key("Enter")?;
-
-    Ok(())
 }
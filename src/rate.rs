@@ -0,0 +1,500 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use crate::task::TaskParams;
+
+/// Decides how long the register loop waits between attempts.
+///
+/// Time is passed in as the time since the loop started, so a policy can be driven by a
+/// simulated clock.
+pub trait RatePolicy: Send {
+    /// Records whether the attempt made at `now` succeeded
+    fn record(&mut self, now: Duration, success: bool);
+    /// Delay before the next attempt
+    fn next_delay(&mut self, now: Duration) -> Duration;
+}
+
+/// Narrows the delay down between a lower and an upper bound, lowering the upper bound after a
+/// run of successes and raising the lower bound after a run of failures
+pub struct Bisection {
+    lower: Duration,
+    upper: Duration,
+    wait: Duration,
+    min: Duration,
+    max: Duration,
+    consecutive_successes: u32,
+    consecutive_failures: u32,
+}
+
+impl Bisection {
+    const SUCCESS_THRESHOLD: u32 = 5;
+    const FAILURE_THRESHOLD: u32 = 3;
+
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Bisection {
+            lower: min,
+            upper: max,
+            wait: (min + max) / 2,
+            min,
+            max,
+            consecutive_successes: 0,
+            consecutive_failures: 0,
+        }
+    }
+}
+
+impl RatePolicy for Bisection {
+    fn record(&mut self, _now: Duration, success: bool) {
+        if success {
+            self.consecutive_successes += 1;
+            self.consecutive_failures = 0;
+            if self.consecutive_successes >= Self::SUCCESS_THRESHOLD {
+                self.upper = self.wait;
+                self.wait = (self.lower + self.upper) / 2;
+                self.consecutive_successes = 0;
+            }
+        } else {
+            self.consecutive_failures += 1;
+            self.consecutive_successes = 0;
+            if self.consecutive_failures >= Self::FAILURE_THRESHOLD {
+                self.lower = self.wait;
+                self.wait = (self.lower + self.upper) / 2;
+                self.consecutive_failures = 0;
+            }
+        }
+    }
+
+    fn next_delay(&mut self, _now: Duration) -> Duration {
+        self.wait.clamp(self.min, self.max)
+    }
+}
+
+/// Additive increase, multiplicative decrease: every success shortens the delay by `step`,
+/// every failure multiplies it by `factor`
+pub struct Aimd {
+    interval: Duration,
+    min: Duration,
+    max: Duration,
+    step: Duration,
+    factor: f64,
+}
+
+impl Aimd {
+    pub fn new(
+        interval: Duration,
+        min: Duration,
+        max: Duration,
+        step: Duration,
+        factor: f64,
+    ) -> Self {
+        Aimd {
+            interval: interval.clamp(min, max),
+            min,
+            max,
+            step,
+            factor,
+        }
+    }
+}
+
+impl RatePolicy for Aimd {
+    fn record(&mut self, _now: Duration, success: bool) {
+        self.interval = match success {
+            true => self.interval.saturating_sub(self.step),
+            false => self.interval.mul_f64(self.factor),
+        }
+        .clamp(self.min, self.max);
+    }
+
+    fn next_delay(&mut self, _now: Duration) -> Duration {
+        self.interval
+    }
+}
+
+/// Allows up to `capacity` attempts at once, refilled at `per_second` attempts per second
+pub struct TokenBucket {
+    per_second: f64,
+    capacity: f64,
+    tokens: f64,
+    last: Duration,
+}
+
+impl TokenBucket {
+    pub fn new(per_second: f64, capacity: f64) -> Self {
+        TokenBucket {
+            per_second,
+            capacity,
+            tokens: capacity,
+            last: Duration::ZERO,
+        }
+    }
+
+    fn refill(&mut self, now: Duration) {
+        let elapsed = now.saturating_sub(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.last = self.last.max(now);
+    }
+}
+
+impl RatePolicy for TokenBucket {
+    fn record(&mut self, now: Duration, _success: bool) {
+        self.refill(now);
+        self.tokens -= 1.0;
+    }
+
+    fn next_delay(&mut self, now: Duration) -> Duration {
+        self.refill(now);
+        match self.tokens >= 1.0 {
+            true => Duration::ZERO,
+            false => Duration::from_secs_f64((1.0 - self.tokens) / self.per_second),
+        }
+    }
+}
+
+/// Waits `interval` plus a random delay of up to `jitter`
+pub struct FixedJitter {
+    interval: Duration,
+    jitter: Duration,
+}
+
+impl FixedJitter {
+    pub fn new(interval: Duration, jitter: Duration) -> Self {
+        FixedJitter { interval, jitter }
+    }
+}
+
+impl RatePolicy for FixedJitter {
+    fn record(&mut self, _now: Duration, _success: bool) {}
+
+    fn next_delay(&mut self, _now: Duration) -> Duration {
+        self.interval + self.jitter.mul_f64(rand::random::<f64>())
+    }
+}
+
+/// Makes `count` attempts `interval` apart when the window opens, then doubles the delay from
+/// `initial` on every failure up to `max`
+pub struct BurstThenBackoff {
+    remaining: u32,
+    interval: Duration,
+    delay: Duration,
+    max: Duration,
+}
+
+impl BurstThenBackoff {
+    pub fn new(count: u32, interval: Duration, initial: Duration, max: Duration) -> Self {
+        BurstThenBackoff {
+            remaining: count,
+            interval,
+            delay: initial.min(max),
+            max,
+        }
+    }
+}
+
+impl RatePolicy for BurstThenBackoff {
+    fn record(&mut self, _now: Duration, success: bool) {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+        } else if !success {
+            self.delay = self.delay.saturating_mul(2).min(self.max);
+        }
+    }
+
+    fn next_delay(&mut self, _now: Duration) -> Duration {
+        match self.remaining > 0 {
+            true => self.interval,
+            false => self.delay,
+        }
+    }
+}
+
+/// Rate policy chosen for a register node, read from its `rate` parameter and the `rate_*`
+/// parameters of that policy
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RateConfig {
+    Bisection {
+        min: Duration,
+        max: Duration,
+    },
+    Aimd {
+        interval: Duration,
+        min: Duration,
+        max: Duration,
+        step: Duration,
+        factor: f64,
+    },
+    TokenBucket {
+        per_second: f64,
+        capacity: f64,
+    },
+    FixedJitter {
+        interval: Duration,
+        jitter: Duration,
+    },
+    BurstThenBackoff {
+        count: u32,
+        interval: Duration,
+        initial: Duration,
+        max: Duration,
+    },
+}
+
+impl RateConfig {
+    pub fn from_params(params: &TaskParams) -> Result<Self> {
+        let number = |key: &str, default: f64| -> Result<f64> {
+            match params.get(key) {
+                Some(value) => value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|value| value.is_finite() && *value >= 0.0)
+                    .ok_or_else(|| anyhow!("Invalid number `{value}` for `{key}`")),
+                None => Ok(default),
+            }
+        };
+        let secs = |key: &str, default: f64| -> Result<Duration> {
+            Ok(Duration::from_secs_f64(number(key, default)?))
+        };
+        let config = match params.get("rate").unwrap_or("bisection") {
+            "bisection" => RateConfig::Bisection {
+                min: secs("rate_min", 0.1)?,
+                max: secs("rate_max", 10.0)?,
+            },
+            "aimd" => RateConfig::Aimd {
+                interval: secs("rate_interval", 1.0)?,
+                min: secs("rate_min", 0.1)?,
+                max: secs("rate_max", 10.0)?,
+                step: secs("rate_step", 0.1)?,
+                factor: number("rate_factor", 2.0)?,
+            },
+            "token_bucket" => {
+                let per_second = number("rate_per_second", 1.0)?;
+                if per_second <= 0.0 {
+                    return Err(anyhow!("`rate_per_second` must be above 0"));
+                }
+                RateConfig::TokenBucket {
+                    per_second,
+                    capacity: number("rate_capacity", 5.0)?.max(1.0),
+                }
+            }
+            "fixed" => RateConfig::FixedJitter {
+                interval: secs("rate_interval", 1.0)?,
+                jitter: secs("rate_jitter", 0.0)?,
+            },
+            "burst" => RateConfig::BurstThenBackoff {
+                count: number("rate_burst", 20.0)? as u32,
+                interval: secs("rate_interval", 0.1)?,
+                initial: secs("rate_min", 1.0)?,
+                max: secs("rate_max", 10.0)?,
+            },
+            rate => return Err(anyhow!("Unknown rate policy `{rate}`")),
+        };
+        match config {
+            RateConfig::Bisection { min, max }
+            | RateConfig::Aimd { min, max, .. }
+            | RateConfig::BurstThenBackoff {
+                initial: min, max, ..
+            } if min > max => Err(anyhow!("`rate_min` must not be above `rate_max`")),
+            config => Ok(config),
+        }
+    }
+
+    /// A fresh policy, starting from its initial state
+    pub fn policy(&self) -> Box<dyn RatePolicy> {
+        match *self {
+            RateConfig::Bisection { min, max } => Box::new(Bisection::new(min, max)),
+            RateConfig::Aimd {
+                interval,
+                min,
+                max,
+                step,
+                factor,
+            } => Box::new(Aimd::new(interval, min, max, step, factor)),
+            RateConfig::TokenBucket {
+                per_second,
+                capacity,
+            } => Box::new(TokenBucket::new(per_second, capacity)),
+            RateConfig::FixedJitter { interval, jitter } => {
+                Box::new(FixedJitter::new(interval, jitter))
+            }
+            RateConfig::BurstThenBackoff {
+                count,
+                interval,
+                initial,
+                max,
+            } => Box::new(BurstThenBackoff::new(count, interval, initial, max)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> TaskParams {
+        TaskParams::new(
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    #[test]
+    fn bisection_narrows_after_runs() {
+        let mut policy = Bisection::new(secs(1.0), secs(9.0));
+        assert_eq!(policy.next_delay(secs(0.0)), secs(5.0));
+
+        // A success breaks the run of failures
+        policy.record(secs(1.0), false);
+        policy.record(secs(2.0), false);
+        policy.record(secs(3.0), true);
+        policy.record(secs(4.0), false);
+        assert_eq!(policy.next_delay(secs(4.0)), secs(5.0));
+
+        policy.record(secs(5.0), false);
+        policy.record(secs(6.0), false);
+        assert_eq!(policy.next_delay(secs(6.0)), secs(7.0));
+
+        for now in 7..12 {
+            policy.record(secs(now as f64), true);
+        }
+        assert_eq!(policy.next_delay(secs(12.0)), secs(6.0));
+    }
+
+    #[test]
+    fn aimd_steps_down_and_multiplies_up() {
+        let mut policy = Aimd::new(secs(2.0), secs(1.0), secs(8.0), secs(0.5), 2.0);
+        policy.record(secs(0.0), true);
+        assert_eq!(policy.next_delay(secs(0.0)), secs(1.5));
+        policy.record(secs(1.5), false);
+        assert_eq!(policy.next_delay(secs(1.5)), secs(3.0));
+        policy.record(secs(4.5), false);
+        policy.record(secs(10.5), false);
+        assert_eq!(policy.next_delay(secs(10.5)), secs(8.0));
+        for now in 0..20 {
+            policy.record(secs(20.0 + now as f64), true);
+        }
+        assert_eq!(policy.next_delay(secs(40.0)), secs(1.0));
+    }
+
+    #[test]
+    fn token_bucket_refills_over_time() {
+        let mut policy = TokenBucket::new(2.0, 3.0);
+        for _ in 0..3 {
+            assert_eq!(policy.next_delay(secs(0.0)), Duration::ZERO);
+            policy.record(secs(0.0), true);
+        }
+        assert_eq!(policy.next_delay(secs(0.0)), secs(0.5));
+        assert_eq!(policy.next_delay(secs(0.25)), secs(0.25));
+        assert_eq!(policy.next_delay(secs(0.5)), Duration::ZERO);
+
+        // Idle time doesn't fill the bucket past its capacity
+        for _ in 0..3 {
+            policy.record(secs(100.0), false);
+        }
+        assert_eq!(policy.next_delay(secs(100.0)), secs(0.5));
+    }
+
+    #[test]
+    fn fixed_jitter_stays_within_the_jitter() {
+        let mut policy = FixedJitter::new(secs(1.0), secs(0.5));
+        for now in 0..100 {
+            let now = secs(now as f64);
+            policy.record(now, now.as_secs().is_multiple_of(2));
+            let delay = policy.next_delay(now);
+            assert!(delay >= secs(1.0) && delay <= secs(1.5), "{delay:?}");
+        }
+
+        let mut policy = FixedJitter::new(secs(1.0), Duration::ZERO);
+        assert_eq!(policy.next_delay(secs(0.0)), secs(1.0));
+    }
+
+    #[test]
+    fn burst_then_backs_off_on_failures() {
+        let mut policy = BurstThenBackoff::new(3, secs(0.1), secs(1.0), secs(5.0));
+        let mut now = Duration::ZERO;
+        for _ in 0..3 {
+            assert_eq!(policy.next_delay(now), secs(0.1));
+            policy.record(now, false);
+            now += secs(0.1);
+        }
+        assert_eq!(policy.next_delay(now), secs(1.0));
+
+        for expected in [2.0, 4.0, 5.0, 5.0] {
+            policy.record(now, false);
+            now += policy.next_delay(now);
+            assert_eq!(policy.next_delay(now), secs(expected));
+        }
+        policy.record(now, true);
+        assert_eq!(policy.next_delay(now), secs(5.0));
+    }
+
+    #[test]
+    fn reads_the_policy_from_params() {
+        assert_eq!(
+            RateConfig::from_params(&params(&[])).unwrap(),
+            RateConfig::Bisection {
+                min: secs(0.1),
+                max: secs(10.0),
+            }
+        );
+        assert_eq!(
+            RateConfig::from_params(&params(&[
+                ("rate", "burst"),
+                ("rate_burst", "5"),
+                ("rate_min", "2"),
+            ]))
+            .unwrap(),
+            RateConfig::BurstThenBackoff {
+                count: 5,
+                interval: secs(0.1),
+                initial: secs(2.0),
+                max: secs(10.0),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_params() {
+        let error = |pairs: &[(&str, &str)]| {
+            RateConfig::from_params(&params(pairs))
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(&[("rate_min", "5"), ("rate_max", "1")]),
+            "`rate_min` must not be above `rate_max`"
+        );
+        assert_eq!(
+            error(&[("rate", "aimd"), ("rate_min", "5"), ("rate_max", "1")]),
+            "`rate_min` must not be above `rate_max`"
+        );
+        assert_eq!(
+            error(&[("rate", "burst"), ("rate_min", "20")]),
+            "`rate_min` must not be above `rate_max`"
+        );
+        assert_eq!(
+            error(&[("rate", "exponential")]),
+            "Unknown rate policy `exponential`"
+        );
+        assert_eq!(
+            error(&[("rate", "token_bucket"), ("rate_per_second", "0")]),
+            "`rate_per_second` must be above 0"
+        );
+        assert_eq!(
+            error(&[("rate_max", "-1")]),
+            "Invalid number `-1` for `rate_max`"
+        );
+        assert_eq!(
+            error(&[("rate", "fixed"), ("rate_jitter", "NaN")]),
+            "Invalid number `NaN` for `rate_jitter`"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// How a task execution ended
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    Succeeded,
+    Failed,
+    TimedOut,
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskStatus::Succeeded => write!(f, "succeeded"),
+            TaskStatus::Failed => write!(f, "failed"),
+            TaskStatus::TimedOut => write!(f, "timed out"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TaskOutcome {
+    pub status: TaskStatus,
+    /// Elapsed time in seconds
+    pub elapsed: f64,
+}
+
+impl TaskOutcome {
+    pub fn new(status: TaskStatus, elapsed: Duration) -> Self {
+        TaskOutcome {
+            status,
+            elapsed: elapsed.as_secs_f64(),
+        }
+    }
+}
+
+impl fmt::Display for TaskOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in {:.1}s", self.status, self.elapsed)
+    }
+}
+
+/// Latest outcome of every task in the run, keyed by task name, persisted so the graph can be
+/// annotated later
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RunReport {
+    #[serde(default)]
+    pub outcomes: BTreeMap<String, TaskOutcome>,
+}
+
+impl RunReport {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read run report {}: {e}", path.display()))?;
+        toml::from_str(&contents).map_err(|e| anyhow!("Failed to parse run report: {e}"))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn record(&mut self, name: &str, outcome: TaskOutcome) {
+        self.outcomes.insert(name.to_string(), outcome);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TaskOutcome> {
+        self.outcomes.get(name)
+    }
+}
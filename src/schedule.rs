@@ -0,0 +1,255 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use tokio::time::{sleep, Instant};
+
+use crate::browser::PLAN_PATH;
+use crate::clock::ServerClock;
+use crate::driver::Page;
+
+/// How long before the window opens the session is left alone, so no request is in flight at T-0
+const FINAL_STRETCH: Duration = Duration::from_secs(5);
+
+/// When the registration window opens, the register tasks wait for it with the session kept
+/// warm
+#[derive(Copy, Clone, Debug)]
+pub struct Schedule {
+    pub opens_at: DateTime<Tz>,
+    /// How often the session is pinged while waiting
+    pub warm_interval: Duration,
+}
+
+impl Schedule {
+    /// Reads `REGISTRATION_OPENS` in the `REGISTRATION_TIMEZONE` (defaulting to
+    /// `America/Toronto`) and `SESSION_WARM_INTERVAL` in seconds, `None` when no opening time
+    /// is set
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(opens) = dotenv::var("REGISTRATION_OPENS") else {
+            return Ok(None);
+        };
+        let timezone =
+            dotenv::var("REGISTRATION_TIMEZONE").unwrap_or_else(|_| "America/Toronto".to_string());
+        let timezone = Tz::from_str(&timezone)
+            .map_err(|e| anyhow!("Invalid `REGISTRATION_TIMEZONE` {timezone}: {e}"))?;
+        let warm_interval = match dotenv::var("SESSION_WARM_INTERVAL") {
+            Ok(interval) => Duration::try_from_secs_f64(interval.parse()?)?,
+            Err(_) => Duration::from_secs(120),
+        };
+        Ok(Some(Schedule {
+            opens_at: parse_time(&opens, timezone)?,
+            warm_interval,
+        }))
+    }
+
+    /// Time left at `now` until the window opens, zero once it is open
+    pub fn remaining(&self, now: DateTime<Utc>) -> Duration {
+        (self.opens_at.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or_default()
+    }
+
+    /// Waits until the window opens by the server's `clock`, pinging the session every
+    /// `warm_interval` until shortly before
+    pub async fn wait(&self, page: &Arc<dyn Page>, clock: &ServerClock) -> Result<()> {
+        let mut last_warm = Instant::now();
+        loop {
+            let remaining = self.remaining(clock.now());
+            if remaining <= FINAL_STRETCH {
+                sleep(remaining).await;
+                println!("Registration window opened at {}", self.opens_at);
+                return Ok(());
+            }
+            println!(
+                "Registration opens at {}, {}s left",
+                self.opens_at,
+                remaining.as_secs()
+            );
+            if last_warm.elapsed() >= self.warm_interval {
+                match warm(page) {
+                    Ok(false) => eprintln!("The session was signed out while waiting"),
+                    Ok(true) => {}
+                    Err(e) => eprintln!("Failed to keep the session warm: {e}"),
+                }
+                last_warm = Instant::now();
+            }
+            sleep((remaining - FINAL_STRETCH).min(self.warm_interval)).await;
+        }
+    }
+}
+
+/// Loads the plan in the background, which counts as activity for the session. `false` once
+/// the session is gone and the request was refused or sent to the sign-in page.
+pub fn warm(page: &Arc<dyn Page>) -> Result<bool> {
+    let alive = page.evaluate(&format!(
+        r#"fetch("{PLAN_PATH}/CurrentAsync", {{ credentials: "include" }}).then(response => response.ok && !response.redirected)"#
+    ))?;
+    // Drivers that can't run the script can't tell either way
+    Ok(alive.and_then(|alive| alive.as_bool()).unwrap_or(true))
+}
+
+/// Parses an RFC 3339 timestamp, or a local time like `2025-07-15 07:00` in `timezone`
+fn parse_time(time: &str, timezone: Tz) -> Result<DateTime<Tz>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+        return Ok(time.with_timezone(&timezone));
+    }
+    let naive = [
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(time, format).ok())
+    .ok_or_else(|| {
+        anyhow!("Invalid `REGISTRATION_OPENS` {time}, expected e.g. 2025-07-15 07:00")
+    })?;
+    timezone
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| anyhow!("{time} does not exist in {timezone}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::driver::mock::{MockPage, MockScript};
+
+    fn toronto(time: &str) -> Result<String> {
+        Ok(parse_time(time, chrono_tz::America::Toronto)?.to_rfc3339())
+    }
+
+    #[test]
+    fn parses_local_times_in_the_timezone() {
+        assert_eq!(
+            toronto("2025-01-15 07:00").unwrap(),
+            "2025-01-15T07:00:00-05:00"
+        );
+        assert_eq!(
+            toronto("2025-07-15 07:00").unwrap(),
+            "2025-07-15T07:00:00-04:00"
+        );
+        assert_eq!(
+            toronto("2025-07-15T07:00:30.5").unwrap(),
+            "2025-07-15T07:00:30.500-04:00"
+        );
+        assert_eq!(
+            parse_time("2025-07-15 07:00", chrono_tz::Europe::London)
+                .unwrap()
+                .to_rfc3339(),
+            "2025-07-15T07:00:00+01:00"
+        );
+    }
+
+    #[test]
+    fn converts_rfc_3339_times_into_the_timezone() {
+        assert_eq!(
+            toronto("2025-07-15T11:00:00Z").unwrap(),
+            "2025-07-15T07:00:00-04:00"
+        );
+    }
+
+    #[test]
+    fn handles_daylight_saving_changes() {
+        // Clocks skip from 2:00 to 3:00, so 2:30 never happens
+        let error = toronto("2025-03-09 02:30").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "2025-03-09 02:30 does not exist in America/Toronto"
+        );
+        // Clocks go back from 2:00 to 1:00, so 1:30 happens twice and the first is taken
+        assert_eq!(
+            toronto("2025-11-02 01:30").unwrap(),
+            "2025-11-02T01:30:00-04:00"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_times() {
+        for time in ["", "tomorrow", "2025-07-15", "2025-13-01 07:00", "07:00"] {
+            assert!(toronto(time).is_err(), "{time}");
+        }
+    }
+
+    #[test]
+    fn reads_the_schedule_from_env() {
+        std::env::remove_var("REGISTRATION_OPENS");
+        assert!(Schedule::from_env().unwrap().is_none());
+
+        std::env::set_var("REGISTRATION_OPENS", "2025-07-15 07:00");
+        std::env::set_var("REGISTRATION_TIMEZONE", "America/Vancouver");
+        std::env::set_var("SESSION_WARM_INTERVAL", "30");
+        let schedule = Schedule::from_env().unwrap().unwrap();
+        assert_eq!(schedule.opens_at.to_rfc3339(), "2025-07-15T07:00:00-07:00");
+        assert_eq!(schedule.warm_interval, Duration::from_secs(30));
+
+        std::env::remove_var("SESSION_WARM_INTERVAL");
+        std::env::remove_var("REGISTRATION_TIMEZONE");
+        let schedule = Schedule::from_env().unwrap().unwrap();
+        assert_eq!(schedule.opens_at.to_rfc3339(), "2025-07-15T07:00:00-04:00");
+        assert_eq!(schedule.warm_interval, Duration::from_secs(120));
+
+        std::env::set_var("REGISTRATION_TIMEZONE", "Guelph");
+        assert!(Schedule::from_env().is_err());
+        std::env::remove_var("REGISTRATION_TIMEZONE");
+        std::env::set_var("SESSION_WARM_INTERVAL", "-1");
+        assert!(Schedule::from_env().is_err());
+        std::env::remove_var("SESSION_WARM_INTERVAL");
+        std::env::set_var("REGISTRATION_OPENS", "soon");
+        assert!(Schedule::from_env().is_err());
+        std::env::remove_var("REGISTRATION_OPENS");
+    }
+
+    fn opening_in(delay: TimeDelta) -> Schedule {
+        Schedule {
+            opens_at: (Utc::now() + delay).with_timezone(&chrono_tz::America::Toronto),
+            warm_interval: Duration::from_millis(100),
+        }
+    }
+
+    #[test]
+    fn remaining_time_stops_at_zero() {
+        let schedule = opening_in(TimeDelta::seconds(60));
+        let remaining = schedule.remaining(Utc::now());
+        assert!(remaining > Duration::from_secs(59) && remaining <= Duration::from_secs(60));
+        assert_eq!(
+            schedule.remaining(Utc::now() + TimeDelta::seconds(61)),
+            Duration::ZERO
+        );
+    }
+
+    #[tokio::test]
+    async fn waits_until_the_window_opens() {
+        let page: Arc<dyn Page> = Arc::new(MockPage::new(MockScript::default()));
+        let clock = ServerClock::default();
+
+        let started = Instant::now();
+        opening_in(TimeDelta::seconds(-60))
+            .wait(&page, &clock)
+            .await
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_millis(50));
+
+        let started = Instant::now();
+        opening_in(TimeDelta::milliseconds(500))
+            .wait(&page, &clock)
+            .await
+            .unwrap();
+        let waited = started.elapsed();
+        assert!(
+            waited >= Duration::from_millis(450) && waited < Duration::from_secs(1),
+            "{waited:?}"
+        );
+    }
+
+    #[test]
+    fn warming_without_a_script_result_assumes_the_session_is_alive() {
+        let page: Arc<dyn Page> = Arc::new(MockPage::new(MockScript::default()));
+        assert!(warm(&page).unwrap());
+    }
+}
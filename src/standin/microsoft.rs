@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use otpauth::TOTP;
+
+use crate::standin::{escape_html, Handler, Request, Response};
+
+/// Cookie set once the sign-in completes, checked by the WebAdvisor stand-in. Every sign-in gets
+/// a new value, so a timed out session stays signed out.
+pub const SESSION_COOKIE: &str = "standin_session";
+
+const PERIOD: u64 = 30;
+
+/// Sessions issued by the sign-in, by cookie value, with the time of their last activity
+#[derive(Clone, Debug, Default)]
+pub struct Sessions(Arc<Mutex<HashMap<String, Instant>>>);
+
+impl Sessions {
+    /// Starts a new session, returning its cookie value
+    pub fn issue(&self) -> String {
+        let id = format!("{:016x}", rand::random::<u64>());
+        self.0.lock().unwrap().insert(id.clone(), Instant::now());
+        id
+    }
+
+    /// Whether `id` was issued and, with a `timeout`, has been active within it. A live session
+    /// counts this as activity.
+    pub fn touch(&self, id: &str, timeout: Option<Duration>) -> bool {
+        let mut sessions = self.0.lock().unwrap();
+        let Some(last_active) = sessions.get_mut(id) else {
+            return false;
+        };
+        if timeout.is_some_and(|timeout| last_active.elapsed() > timeout) {
+            return false;
+        }
+        *last_active = Instant::now();
+        true
+    }
+}
+
+/// Stand-in for the Microsoft sign-in pages, validating the password and the TOTP code.
+///
+/// The flow is stateless apart from the sessions it issues: each page posts the email and the
+/// URL to return to as hidden fields.
+pub struct MicrosoftStandin {
+    password: String,
+    totp: TOTP,
+    sessions: Sessions,
+}
+
+enum Code {
+    Valid,
+    Expired,
+    Invalid,
+}
+
+impl MicrosoftStandin {
+    pub fn new(password: &str, otp_secret: &str) -> Result<Self> {
+        let totp = TOTP::from_base32(otp_secret)
+            .ok_or_else(|| anyhow!("OTP secret is not valid base32"))?;
+        Ok(MicrosoftStandin {
+            password: password.to_string(),
+            totp,
+            sessions: Sessions::default(),
+        })
+    }
+
+    /// Sessions this sign-in issues, shared with the stand-in that checks them
+    pub fn sessions(&self) -> Sessions {
+        self.sessions.clone()
+    }
+
+    /// Reads `STANDIN_PASSWORD` and `STANDIN_OTP_SECRET`, defaulting to `PASSWORD` and `OTP`
+    pub fn from_env() -> Result<Self> {
+        let password = dotenv::var("STANDIN_PASSWORD").or_else(|_| dotenv::var("PASSWORD"))?;
+        let secret = dotenv::var("STANDIN_OTP_SECRET").or_else(|_| dotenv::var("OTP"))?;
+        MicrosoftStandin::new(&password, &secret)
+    }
+
+    /// Code shown by an authenticator at `time` (Unix seconds), always 6 digits
+    fn code_at(&self, time: u64) -> String {
+        format!("{:06}", self.totp.generate(PERIOD, time))
+    }
+
+    /// Checks `code` as entered at `now` (Unix seconds), accepting only the exact 6 digits
+    fn check_code(&self, code: &str, now: u64) -> Code {
+        let code = code.trim();
+        if self.code_at(now) == code {
+            Code::Valid
+        } else if (1..=2).any(|step| self.code_at(now.saturating_sub(step * PERIOD)) == code) {
+            Code::Expired
+        } else {
+            Code::Invalid
+        }
+    }
+
+    fn handle_form(&self, path: &str, request: &Request) -> Response {
+        let form = request.form();
+        let field = |name: &str| form.get(name).cloned().unwrap_or_default();
+        let (email, return_to) = (field("login"), field("return_to"));
+        match path {
+            "/login/email" if email.contains('@') => password_page(&email, &return_to, None),
+            "/login/email" => email_page(
+                &return_to,
+                Some("Enter a valid email address, phone number, or Skype name."),
+            ),
+            "/login/password" if field("passwd") == self.password => {
+                otp_page(&email, &return_to, None)
+            }
+            "/login/password" => password_page(
+                &email,
+                &return_to,
+                Some("Your account or password is incorrect."),
+            ),
+            "/login/otp" => match self.check_code(&field("otc"), unix_now()) {
+                Code::Valid => stay_signed_in_page(&email, &return_to),
+                Code::Expired => otp_page(
+                    &email,
+                    &return_to,
+                    Some("That code has expired. Please enter a new code."),
+                ),
+                Code::Invalid => otp_page(
+                    &email,
+                    &return_to,
+                    Some("You didn't enter the expected verification code. Please try again."),
+                ),
+            },
+            "/login/kmsi" => Response::redirect(&return_to).with_header(
+                "Set-Cookie",
+                &format!(
+                    "{SESSION_COOKIE}={}; Path=/; HttpOnly",
+                    self.sessions.issue()
+                ),
+            ),
+            _ => Response::not_found(),
+        }
+    }
+}
+
+impl Handler for MicrosoftStandin {
+    fn handle(&self, request: Request) -> Response {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/login") => {
+                email_page(&request.query_param("return_to").unwrap_or_default(), None)
+            }
+            ("POST", path) => self.handle_form(path, &request),
+            _ => Response::not_found(),
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn page(title: &str, action: &str, hidden: &[(&str, &str)], content: &str) -> Response {
+    let hidden = hidden
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                r#"<input type="hidden" name="{name}" value="{}">"#,
+                escape_html(value)
+            )
+        })
+        .collect::<String>();
+    Response::html(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><title>{title}</title></head>
+<body>
+<form method="post" action="{action}">
+{hidden}
+{content}
+</form>
+</body>
+</html>
+"#
+    ))
+}
+
+fn error(id: &str, message: Option<&str>) -> String {
+    message
+        .map(|message| {
+            format!(
+                r#"<div id="{id}" role="alert">{}</div>"#,
+                escape_html(message)
+            )
+        })
+        .unwrap_or_default()
+}
+
+fn email_page(return_to: &str, message: Option<&str>) -> Response {
+    page(
+        "Sign in to your account",
+        "/login/email",
+        &[("return_to", return_to)],
+        &format!(
+            r#"{}<input type="email" name="login" autofocus>"#,
+            error("usernameError", message)
+        ),
+    )
+}
+
+fn password_page(email: &str, return_to: &str, message: Option<&str>) -> Response {
+    page(
+        "Enter password",
+        "/login/password",
+        &[("login", email), ("return_to", return_to)],
+        &format!(
+            r#"{}<input type="password" name="passwd" autofocus>"#,
+            error("passwordError", message)
+        ),
+    )
+}
+
+fn otp_page(email: &str, return_to: &str, message: Option<&str>) -> Response {
+    page(
+        "Enter code",
+        "/login/otp",
+        &[("login", email), ("return_to", return_to)],
+        &format!(
+            r#"{}<input type="tel" id="idTxtBx_SAOTCC_OTC" name="otc" autofocus>"#,
+            error("idSpan_SAOTCC_Error_OTC", message)
+        ),
+    )
+}
+
+fn stay_signed_in_page(email: &str, return_to: &str) -> Response {
+    page(
+        "Stay signed in?",
+        "/login/kmsi",
+        &[("login", email), ("return_to", return_to)],
+        r#"<input type="checkbox" id="KmsiCheckboxField" name="DontShowAgain">
+<input type="submit" id="idSIButton9" value="Yes">"#,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ServerClock;
+    use crate::driver::mock::test_env;
+    use crate::microsoft::acquire_2fa_code;
+
+    /// Same secret the tests' `OTP` is set to
+    const SECRET: &str = "JBSWY3DPEHPK3PXP";
+
+    fn standin() -> MicrosoftStandin {
+        MicrosoftStandin::new("password", SECRET).unwrap()
+    }
+
+    fn request(method: &str, path: &str, query: &str, fields: &[(&str, &str)]) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query.to_string(),
+            headers: HashMap::new(),
+            body: form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(fields)
+                .finish(),
+        }
+    }
+
+    fn post(standin: &MicrosoftStandin, path: &str, fields: &[(&str, &str)]) -> Response {
+        let mut fields = fields.to_vec();
+        fields.extend([
+            ("login", "student@uoguelph.ca"),
+            ("return_to", "http://wa.test/"),
+        ]);
+        standin.handle(request("POST", path, "", &fields))
+    }
+
+    #[test]
+    fn walks_through_the_sign_in() {
+        let standin = standin();
+        let email = standin.handle(request(
+            "GET",
+            "/login",
+            "return_to=http%3A%2F%2Fwa.test%2F",
+            &[],
+        ));
+        assert!(email.body.contains(r#"type="email""#));
+        assert!(email.body.contains(r#"value="http://wa.test/""#));
+
+        let password = post(&standin, "/login/email", &[]);
+        assert!(password.body.contains(r#"type="password""#));
+        assert!(!password.body.contains("passwordError"));
+
+        let otp = post(&standin, "/login/password", &[("passwd", "password")]);
+        assert!(otp.body.contains("idTxtBx_SAOTCC_OTC"));
+        assert!(!otp.body.contains("idSpan_SAOTCC_Error_OTC"));
+
+        let code = standin.code_at(unix_now());
+        let kmsi = post(&standin, "/login/otp", &[("otc", &code)]);
+        assert!(kmsi.body.contains("KmsiCheckboxField"));
+
+        let signed_in = post(&standin, "/login/kmsi", &[]);
+        assert_eq!(signed_in.status, 302);
+        let cookie = signed_in
+            .headers
+            .iter()
+            .find(|(name, _)| name == "Set-Cookie")
+            .map(|(_, value)| value.clone())
+            .unwrap();
+        let session = cookie
+            .strip_prefix(&format!("{SESSION_COOKIE}="))
+            .and_then(|cookie| cookie.split(';').next())
+            .unwrap();
+        assert!(standin.sessions().touch(session, None));
+        assert!(!standin.sessions().touch("forged", None));
+    }
+
+    #[test]
+    fn rejects_a_wrong_email_or_password() {
+        let standin = standin();
+        let email = standin.handle(request(
+            "POST",
+            "/login/email",
+            "",
+            &[("login", "student"), ("return_to", "http://wa.test/")],
+        ));
+        assert!(email.body.contains("Enter a valid email address"));
+        assert!(email.body.contains(r#"type="email""#));
+
+        let password = post(&standin, "/login/password", &[("passwd", "wrong")]);
+        assert!(password
+            .body
+            .contains("Your account or password is incorrect."));
+        assert!(password.body.contains(r#"type="password""#));
+    }
+
+    #[test]
+    fn rejects_expired_and_malformed_codes() {
+        let standin = standin();
+        let now = unix_now();
+        let expired = post(
+            &standin,
+            "/login/otp",
+            &[("otc", &standin.code_at(now - PERIOD))],
+        );
+        // The previous code may equal the current one, in which case it is still accepted
+        if standin.code_at(now - PERIOD) != standin.code_at(unix_now()) {
+            assert!(expired.body.contains("That code has expired."));
+        }
+        for code in ["", "12345", "1234567", "12a456", "code"] {
+            let invalid = post(&standin, "/login/otp", &[("otc", code)]);
+            assert!(
+                invalid
+                    .body
+                    .contains("You didn't enter the expected verification code."),
+                "{code}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_codes_without_their_leading_zeros() {
+        let standin = standin();
+        let time = (0..1_000_000)
+            .map(|step| step * PERIOD)
+            .find(|time| standin.code_at(*time).starts_with('0'))
+            .unwrap();
+        let code = standin.code_at(time);
+        assert!(matches!(standin.check_code(&code, time), Code::Valid));
+        let unpadded = code.trim_start_matches('0');
+        assert!(matches!(standin.check_code(unpadded, time), Code::Invalid));
+    }
+
+    #[tokio::test]
+    async fn accepts_the_code_of_the_otp_task() {
+        test_env();
+        let standin = standin();
+        // Retries once in case the period ended in between
+        for _ in 0..2 {
+            let code = acquire_2fa_code(&ServerClock::default()).await.unwrap();
+            if let Code::Valid = standin.check_code(&code, unix_now()) {
+                return;
+            }
+        }
+        panic!("The OTP task's code was rejected");
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+pub mod microsoft;
+pub mod webadvisor;
+
+/// Request received by a stand-in server
+pub struct Request {
+    pub method: String,
+    /// Path without the query string
+    pub path: String,
+    pub query: String,
+    /// Header names are lowercase
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl Request {
+    /// Fields of the url-encoded form in the body
+    pub fn form(&self) -> HashMap<String, String> {
+        form_urlencoded::parse(self.body.as_bytes())
+            .into_owned()
+            .collect()
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        form_urlencoded::parse(self.query.as_bytes())
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get("cookie")?
+            .split(';')
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &str, body: impl Into<String>) -> Self {
+        Response {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body: body.into(),
+        }
+    }
+
+    pub fn html(body: impl Into<String>) -> Self {
+        Response::new(200, "text/html; charset=utf-8", body)
+    }
+
+    pub fn text(status: u16, body: impl Into<String>) -> Self {
+        Response::new(status, "text/plain; charset=utf-8", body)
+    }
+
+    pub fn json(status: u16, body: &serde_json::Value) -> Self {
+        Response::new(status, "application/json; charset=utf-8", body.to_string())
+    }
+
+    pub fn redirect(location: &str) -> Self {
+        Response::text(302, "").with_header("Location", location)
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn not_found() -> Self {
+        Response::text(404, "Not found")
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            302 => "Found",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            409 => "Conflict",
+            _ => "Unknown",
+        }
+    }
+}
+
+pub trait Handler: Send + Sync + 'static {
+    fn handle(&self, request: Request) -> Response;
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    let mut parts = line.split_whitespace();
+    let method = parts
+        .next()
+        .ok_or_else(|| anyhow!("Empty request line"))?
+        .to_string();
+    let target = parts
+        .next()
+        .ok_or_else(|| anyhow!("Request line has no target"))?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let (path, query) = (path.to_string(), query.to_string());
+
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).await?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = headers
+        .get("content-length")
+        .map(|length| length.parse::<usize>())
+        .transpose()?
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Request {
+        method,
+        path,
+        query,
+        headers,
+        body: String::from_utf8(body)?,
+    })
+}
+
+async fn respond(mut stream: TcpStream, handler: Arc<dyn Handler>) -> Result<()> {
+    let request = read_request(&mut stream).await?;
+    let response = handler.handle(request);
+    let mut head = format!("HTTP/1.1 {} {}\r\n", response.status, response.reason());
+    for (name, value) in response.headers.iter() {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str(&format!(
+        "Date: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        Utc::now().format("%a, %d %b %Y %H:%M:%S GMT"),
+        response.body.len()
+    ));
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Escapes text for use in HTML content and attribute values
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Percent-encodes text for use in a query string
+pub fn encode_query(text: &str) -> String {
+    form_urlencoded::byte_serialize(text.as_bytes()).collect()
+}
+
+/// Serves `handler` on a free localhost port, returning the base URL it is reachable at.
+///
+/// Used to run the graph end-to-end against local stand-ins of the sites it drives, without
+/// network access.
+pub async fn serve(handler: Arc<dyn Handler>) -> Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let handler = handler.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = respond(stream, handler).await {
+                            eprintln!("Stand-in request failed: {e}");
+                        }
+                    });
+                }
+                Err(e) => eprintln!("Stand-in failed to accept a connection: {e}"),
+            }
+        }
+    });
+    Ok(url)
+}
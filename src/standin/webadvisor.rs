@@ -0,0 +1,527 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde_json::json;
+
+use crate::browser::PLAN_PATH;
+use crate::colleague::{RegisterRequest, RegistrationOutcome, TOKEN_NAME};
+use crate::standin::microsoft::{Sessions, SESSION_COOKIE};
+use crate::standin::{encode_query, escape_html, Handler, Request, Response};
+use crate::wa;
+
+const STUDENT_ID: &str = "0000001";
+
+/// Message Colleague answers with when registering `section` results in `outcome`
+fn outcome_message(section: &str, outcome: RegistrationOutcome) -> String {
+    match outcome {
+        RegistrationOutcome::Registered => format!("Registered for {section}"),
+        RegistrationOutcome::Waitlisted => {
+            format!("{section} is full, you were added to the waitlist")
+        }
+        RegistrationOutcome::Closed => format!("{section} is closed"),
+        RegistrationOutcome::PrerequisiteMissing => {
+            format!("Prerequisite not met for {section}")
+        }
+        RegistrationOutcome::TimeConflict => {
+            format!("{section} has a time conflict with another section")
+        }
+        RegistrationOutcome::NotOpen => "Registration is not open yet".to_string(),
+        RegistrationOutcome::Unknown => format!("Could not register {section}"),
+    }
+}
+
+/// Stand-in for Colleague Self-Service's plan page, with the term navigation and a register
+/// button that enables at a scripted time, plus the JSON endpoints behind it
+pub struct WebAdvisorStandin {
+    terms: Vec<String>,
+    /// Index of the term shown when the page loads
+    current: usize,
+    opens_at: SystemTime,
+    /// Sections planned in every term
+    sections: Vec<String>,
+    /// Scripted outcomes of sections that can't simply be registered
+    outcomes: HashMap<String, RegistrationOutcome>,
+    registrations: Mutex<Vec<String>>,
+    /// Anti-forgery token embedded in the plan page
+    token: String,
+    /// Sign-in page visitors without a session are redirected to, and the sessions it issues
+    sign_in: Option<(String, Sessions)>,
+    /// Inactivity after which a session is signed out
+    session_timeout: Option<Duration>,
+}
+
+impl WebAdvisorStandin {
+    pub fn new(terms: Vec<String>, current: usize, opens_after: Duration) -> Self {
+        WebAdvisorStandin {
+            terms,
+            current,
+            opens_at: SystemTime::now() + opens_after,
+            sections: vec!["CIS*2500*0101".to_string(), "MATH*1200*0102".to_string()],
+            outcomes: HashMap::new(),
+            registrations: Mutex::new(Vec::new()),
+            token: format!("{:032x}", rand::random::<u128>()),
+            sign_in: None,
+            session_timeout: None,
+        }
+    }
+
+    pub fn with_sections(mut self, sections: Vec<String>) -> Self {
+        self.sections = sections;
+        self
+    }
+
+    /// Answers registering `section` with `outcome` instead of registering it
+    pub fn with_outcome(mut self, section: &str, outcome: RegistrationOutcome) -> Self {
+        self.outcomes.insert(section.to_string(), outcome);
+        self
+    }
+
+    /// Requires one of the `sessions` issued by the Microsoft stand-in at `sign_in_url`
+    pub fn with_sign_in(mut self, sign_in_url: &str, sessions: Sessions) -> Self {
+        self.sign_in = Some((sign_in_url.to_string(), sessions));
+        self
+    }
+
+    /// Signs sessions out after `timeout` without a request, showing a dialog to stay signed in
+    /// halfway through
+    pub fn with_session_timeout(mut self, timeout: Duration) -> Self {
+        self.session_timeout = Some(timeout);
+        self
+    }
+
+    fn signed_in(&self, request: &Request) -> bool {
+        let Some((_, sessions)) = &self.sign_in else {
+            return true;
+        };
+        request
+            .cookie(SESSION_COOKIE)
+            .is_some_and(|session| sessions.touch(session, self.session_timeout))
+    }
+
+    /// Reads `STANDIN_TERMS` (comma separated, defaults to the terms of the year in
+    /// `TARGET_SEMESTER`), `STANDIN_REGISTER_DELAY` (seconds until the button enables) and
+    /// `STANDIN_SECTIONS` (comma separated sections planned in every term, each optionally
+    /// followed by `=outcome`) and `STANDIN_SESSION_TIMEOUT` (seconds of inactivity until a
+    /// session is signed out)
+    pub fn from_env() -> Result<Self> {
+        let terms = match dotenv::var("STANDIN_TERMS") {
+            Ok(terms) => terms
+                .split(',')
+                .map(|term| term.trim().to_string())
+                .collect(),
+            Err(_) => {
+                let target = wa::Date::from_str(&dotenv::var("TARGET_SEMESTER")?)?;
+                wa::terms_of_year(target.year)
+            }
+        };
+        let delay = match dotenv::var("STANDIN_REGISTER_DELAY") {
+            Ok(delay) => delay.parse::<f64>()?,
+            Err(_) => 0.0,
+        };
+        let mut standin = WebAdvisorStandin::new(terms, 0, Duration::try_from_secs_f64(delay)?);
+        if let Ok(sections) = dotenv::var("STANDIN_SECTIONS") {
+            let mut planned = Vec::new();
+            for section in sections.split(',') {
+                let (section, outcome) = match section.split_once('=') {
+                    Some((section, outcome)) => (section.trim(), Some(outcome.parse()?)),
+                    None => (section.trim(), None),
+                };
+                if let Some(outcome) = outcome {
+                    standin = standin.with_outcome(section, outcome);
+                }
+                planned.push(section.to_string());
+            }
+            standin = standin.with_sections(planned);
+        }
+        if let Ok(timeout) = dotenv::var("STANDIN_SESSION_TIMEOUT") {
+            standin = standin.with_session_timeout(Duration::try_from_secs_f64(timeout.parse()?)?);
+        }
+        Ok(standin)
+    }
+
+    fn plan_page(&self) -> String {
+        let opens_at = self
+            .opens_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let terms = serde_json::to_string(&self.terms).unwrap_or_else(|_| "[]".to_string());
+        let session_timeout = match self.session_timeout {
+            Some(timeout) => timeout.as_millis().to_string(),
+            None => "null".to_string(),
+        };
+        let registrations: Vec<_> = self
+            .sections
+            .iter()
+            .map(|section| json!({ "SectionId": section, "Action": "Add" }))
+            .collect();
+        let request = json!({ "StudentId": STUDENT_ID, "SectionRegistrations": registrations });
+        let courses: String = self
+            .sections
+            .iter()
+            .map(|section| {
+                let section = escape_html(section);
+                format!(
+                    "<li class=\"schedule-course\" data-section-id=\"{section}\">{section} \
+                     <span class=\"schedule-course-status\">Planned</span></li>\n"
+                )
+            })
+            .collect();
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head><title>Plan &amp; Schedule</title></head>
+<body>
+<input type="hidden" name="{TOKEN_NAME}" value="{token}">
+<button id="schedule-prev-term" type="button">Previous Term</button>
+<span id="schedule-activeterm-text"></span>
+<button id="schedule-next-term" type="button">Next Term</button>
+<button id="register-button" type="button" disabled>Register Now</button>
+<div id="notificationHost"></div>
+<ul id="schedule-courses">
+{courses}</ul>
+<script>
+const terms = {terms};
+let current = {current};
+const text = document.getElementById("schedule-activeterm-text");
+const button = document.getElementById("register-button");
+const show = () => text.innerText = terms[current];
+document.getElementById("schedule-prev-term").onclick = () => {{
+    if (current > 0) {{ current--; show(); }}
+}};
+document.getElementById("schedule-next-term").onclick = () => {{
+    if (current < terms.length - 1) {{ current++; show(); }}
+}};
+setTimeout(() => button.removeAttribute("disabled"), Math.max(0, {opens_at} - Date.now()));
+button.onclick = async () => {{
+    const token = document.querySelector("input[name='{TOKEN_NAME}']").value;
+    const response = await fetch("{PLAN_PATH}/RegisterSections", {{
+        method: "POST",
+        headers: {{ "Content-Type": "application/json", "{TOKEN_NAME}": token }},
+        body: JSON.stringify({request}),
+    }});
+    const result = await response.json();
+    const host = document.getElementById("notificationHost");
+    host.replaceChildren(...result.Messages.map(message => {{
+        const banner = document.createElement("div");
+        banner.className = "notification-message";
+        banner.innerText = message.Message;
+        return banner;
+    }}));
+    for (const message of result.Messages) {{
+        const course = document.querySelector(`.schedule-course[data-section-id="${{message.SectionId}}"]`);
+        const badge = course?.querySelector(".schedule-course-status");
+        if (!badge) continue;
+        if (/waitlist/i.test(message.Message)) badge.innerText = "Waitlisted";
+        else if (/^(already )?registered/i.test(message.Message)) badge.innerText = "Registered";
+    }}
+}};
+const sessionTimeout = {session_timeout};
+let warning;
+const watchSession = () => {{
+    clearTimeout(warning);
+    warning = setTimeout(() => {{
+        const dialog = document.createElement("div");
+        dialog.id = "session-timeout-dialog";
+        dialog.innerHTML = `<p>Your session is about to expire.</p>
+            <button id="session-timeout-continue" type="button">Continue</button>`;
+        dialog.querySelector("button").onclick = () => {{
+            dialog.remove();
+            fetch("{PLAN_PATH}/CurrentAsync");
+            watchSession();
+        }};
+        document.body.append(dialog);
+    }}, sessionTimeout / 2);
+}};
+if (sessionTimeout !== null) watchSession();
+show();
+</script>
+</body>
+</html>
+"#,
+            current = self.current,
+            token = self.token,
+        )
+    }
+
+    fn plan(&self) -> Response {
+        let terms: Vec<_> = self
+            .terms
+            .iter()
+            .filter_map(|term| wa::Date::from_str(term).ok())
+            .map(|term| {
+                let courses: Vec<_> = self
+                    .sections
+                    .iter()
+                    .map(|section| {
+                        let course = section
+                            .rsplit_once('*')
+                            .map_or(section.as_str(), |(course, _)| course);
+                        json!({ "CourseId": course, "SectionId": section })
+                    })
+                    .collect();
+                json!({ "Code": term.code(), "PlannedCourses": courses })
+            })
+            .collect();
+        Response::json(
+            200,
+            &json!({ "Id": 1, "PersonId": STUDENT_ID, "Terms": terms }),
+        )
+    }
+
+    fn register_sections(&self, request: &Request) -> Response {
+        if request.headers.get(&TOKEN_NAME.to_lowercase()) != Some(&self.token) {
+            return Response::text(400, "Missing or invalid anti-forgery token");
+        }
+        let Ok(body) = serde_json::from_str::<RegisterRequest>(&request.body) else {
+            return Response::text(400, "Invalid registration request");
+        };
+        let open = SystemTime::now() >= self.opens_at;
+        let mut registrations = self.registrations.lock().unwrap();
+        let messages: Vec<_> = body
+            .section_registrations
+            .iter()
+            .map(|registration| {
+                let section = &registration.section_id;
+                let message = if !open {
+                    outcome_message(section, RegistrationOutcome::NotOpen)
+                } else if !self.sections.contains(section) {
+                    format!("{section} is not in your plan")
+                } else if let Some(outcome) = self.outcomes.get(section) {
+                    outcome_message(section, *outcome)
+                } else if registrations.contains(section) {
+                    format!("Already registered for {section}")
+                } else {
+                    println!("Stand-in registered for {section}");
+                    registrations.push(section.clone());
+                    format!("Registered for {section}")
+                };
+                json!({ "SectionId": section, "Message": message })
+            })
+            .collect();
+        Response::json(200, &json!({ "Messages": messages }))
+    }
+}
+
+impl Handler for WebAdvisorStandin {
+    fn handle(&self, request: Request) -> Response {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", path) if path == PLAN_PATH && !self.signed_in(&request) => {
+                let host = request.headers.get("host").cloned().unwrap_or_default();
+                let return_to = encode_query(&format!("http://{host}{PLAN_PATH}"));
+                let sign_in_url = self.sign_in.as_ref().map_or("", |(url, _)| url.as_str());
+                Response::redirect(&format!("{sign_in_url}?return_to={return_to}"))
+            }
+            ("GET", path) if path == PLAN_PATH => Response::html(self.plan_page()),
+            _ if !self.signed_in(&request) => Response::text(401, "Not signed in"),
+            ("GET", path) if path == format!("{PLAN_PATH}/CurrentAsync") => self.plan(),
+            ("POST", path) if path == format!("{PLAN_PATH}/RegisterSections") => {
+                self.register_sections(&request)
+            }
+            _ => Response::not_found(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use reqwest::redirect::Policy;
+    use reqwest::StatusCode;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::colleague::SectionRegistration;
+    use crate::standin::serve;
+
+    const CIS: &str = "CIS*2500*0101";
+    const MATH: &str = "MATH*1200*0102";
+
+    fn http() -> reqwest::Client {
+        reqwest::Client::builder()
+            .redirect(Policy::none())
+            .build()
+            .unwrap()
+    }
+
+    fn standin(opens_after: Duration) -> WebAdvisorStandin {
+        WebAdvisorStandin::new(vec!["Fall 2025".to_string()], 0, opens_after)
+    }
+
+    /// Anti-forgery token embedded in the plan page
+    async fn token(url: &str) -> String {
+        let page = http()
+            .get(format!("{url}{PLAN_PATH}"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        let (_, rest) = page
+            .split_once(&format!(r#"name="{TOKEN_NAME}" value=""#))
+            .unwrap();
+        rest.split('"').next().unwrap().to_string()
+    }
+
+    async fn register(url: &str, token: &str, sections: &[&str]) -> reqwest::Response {
+        let request = RegisterRequest {
+            student_id: STUDENT_ID.to_string(),
+            section_registrations: sections
+                .iter()
+                .map(|section| SectionRegistration {
+                    section_id: section.to_string(),
+                    action: "Add".to_string(),
+                })
+                .collect(),
+        };
+        http()
+            .post(format!("{url}{PLAN_PATH}/RegisterSections"))
+            .header(TOKEN_NAME, token)
+            .json(&request)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    /// Message answered for every section, in request order
+    async fn messages(response: reqwest::Response) -> Vec<String> {
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = response.json().await.unwrap();
+        body["Messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|message| message["Message"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn serves_the_plan_page_and_plan() {
+        let url = serve(Arc::new(standin(Duration::ZERO))).await.unwrap();
+        let page = http()
+            .get(format!("{url}{PLAN_PATH}"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(page.status(), StatusCode::OK);
+        assert!(page.headers().contains_key("date"));
+        let page = page.text().await.unwrap();
+        for id in [
+            "schedule-prev-term",
+            "schedule-activeterm-text",
+            "schedule-next-term",
+            "register-button",
+        ] {
+            assert!(page.contains(&format!(r#"id="{id}""#)), "missing #{id}");
+        }
+        assert!(page.contains(&format!(r#"data-section-id="{CIS}""#)));
+
+        let plan: Value = http()
+            .get(format!("{url}{PLAN_PATH}/CurrentAsync"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(plan["PersonId"], STUDENT_ID);
+        assert_eq!(plan["Terms"][0]["Code"], "F25");
+        assert_eq!(plan["Terms"][0]["PlannedCourses"][0]["SectionId"], CIS);
+
+        let missing = http().get(format!("{url}/nowhere")).send().await.unwrap();
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn registers_once_the_window_opens() {
+        let standin =
+            standin(Duration::from_millis(300)).with_outcome(MATH, RegistrationOutcome::Waitlisted);
+        let url = serve(Arc::new(standin)).await.unwrap();
+        let token = token(&url).await;
+
+        let early = messages(register(&url, &token, &[CIS]).await).await;
+        assert_eq!(early, vec!["Registration is not open yet"]);
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        let open = messages(register(&url, &token, &[CIS, MATH, "ENGL*1000*0101"]).await).await;
+        assert_eq!(
+            open,
+            vec![
+                format!("Registered for {CIS}"),
+                format!("{MATH} is full, you were added to the waitlist"),
+                "ENGL*1000*0101 is not in your plan".to_string(),
+            ]
+        );
+        let again = messages(register(&url, &token, &[CIS]).await).await;
+        assert_eq!(again, vec![format!("Already registered for {CIS}")]);
+    }
+
+    #[tokio::test]
+    async fn rejects_a_missing_or_wrong_token() {
+        let url = serve(Arc::new(standin(Duration::ZERO))).await.unwrap();
+        let wrong = register(&url, "not-the-token", &[CIS]).await;
+        assert_eq!(wrong.status(), StatusCode::BAD_REQUEST);
+        let missing = http()
+            .post(format!("{url}{PLAN_PATH}/RegisterSections"))
+            .body("{}")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), StatusCode::BAD_REQUEST);
+        let right = register(&url, &token(&url).await, &[CIS]).await;
+        assert_eq!(right.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn sessions_come_from_the_sign_in_and_time_out() {
+        let sessions = Sessions::default();
+        let standin = standin(Duration::ZERO)
+            .with_sign_in("http://sign-in.test/login", sessions.clone())
+            .with_session_timeout(Duration::from_millis(300));
+        let url = serve(Arc::new(standin)).await.unwrap();
+        let get = |path: &str, session: &str| {
+            http()
+                .get(format!("{url}{path}"))
+                .header("Cookie", format!("{SESSION_COOKIE}={session}"))
+                .send()
+        };
+        let plan = format!("{PLAN_PATH}/CurrentAsync");
+
+        let forged = get(PLAN_PATH, "forged").await.unwrap();
+        assert_eq!(forged.status(), StatusCode::FOUND);
+        let location = forged.headers()["location"].to_str().unwrap();
+        assert!(location.starts_with("http://sign-in.test/login?return_to="));
+        assert_eq!(
+            get(&plan, "forged").await.unwrap().status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let session = sessions.issue();
+        assert_eq!(
+            get(PLAN_PATH, &session).await.unwrap().status(),
+            StatusCode::OK
+        );
+        // Activity keeps the session alive past the timeout
+        for _ in 0..3 {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            assert_eq!(get(&plan, &session).await.unwrap().status(), StatusCode::OK);
+        }
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(
+            get(&plan, &session).await.unwrap().status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get(PLAN_PATH, &session).await.unwrap().status(),
+            StatusCode::FOUND
+        );
+
+        let fresh = sessions.issue();
+        assert_eq!(get(&plan, &fresh).await.unwrap().status(), StatusCode::OK);
+    }
+}
@@ -1,16 +1,102 @@
-use crate::Context;
-use anyhow::Result;
+use crate::context::Context;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 pub struct Task {
     pub name: String,
-    pub func:
-        Arc<dyn Fn(Context) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send + Sync>,
-    pub pre_condition:
-        Arc<dyn Fn(Context) -> Pin<Box<dyn Future<Output = Result<bool>> + Send>> + Send + Sync>,
+    pub func: TaskFn,
+    pub pre_condition: Condition,
     pub repeatable: bool,
+    /// Whether the run is complete once this task succeeds
+    pub terminal: bool,
+    /// Whether all outgoing tasks start concurrently, each in its own tab
+    pub fork: bool,
+    /// Whether forked branches wait for each other at this task
+    pub join: bool,
+    pub policy: TaskPolicy,
+}
+
+/// How long to wait between attempts of a task
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Backoff {
+    Fixed(Duration),
+    /// Doubles the delay on every retry, up to `max`
+    Exponential {
+        initial: Duration,
+        max: Duration,
+    },
+    /// Exponential backoff with a random delay between zero and the exponential delay
+    Jittered {
+        initial: Duration,
+        max: Duration,
+    },
+}
+
+impl Backoff {
+    /// Delay before the given retry, where the first retry is `1`
+    pub fn delay(&self, retry: u32) -> Duration {
+        let exponential = |initial: Duration, max: Duration| {
+            initial
+                .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+                .min(max)
+        };
+        match *self {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => exponential(initial, max),
+            Backoff::Jittered { initial, max } => {
+                exponential(initial, max).mul_f64(rand::random::<f64>())
+            }
+        }
+    }
+}
+
+/// Timing and retry behaviour of a task, honoured by the executor
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TaskPolicy {
+    /// How long the precondition may take to become true
+    pub pre_condition_timeout: Duration,
+    /// How often the precondition is polled while it is false
+    pub pre_condition_poll: Duration,
+    /// Maximum run time of the task function, `None` lets it run indefinitely
+    pub timeout: Option<Duration>,
+    pub max_attempts: u32,
+    pub backoff: Backoff,
+    /// How long to wait for one of the following tasks to become ready
+    pub branch_timeout: Duration,
+    /// How often the following tasks are polled while waiting
+    pub branch_poll: Duration,
+}
+
+impl Default for TaskPolicy {
+    fn default() -> Self {
+        TaskPolicy {
+            pre_condition_timeout: Duration::from_secs(5),
+            pre_condition_poll: Duration::from_millis(100),
+            timeout: Some(Duration::from_secs(20)),
+            max_attempts: 1,
+            backoff: Backoff::Fixed(Duration::from_secs(1)),
+            branch_timeout: Duration::from_secs(20),
+            branch_poll: Duration::from_millis(10),
+        }
+    }
+}
+
+impl TaskPolicy {
+    /// Default policy for a task, repeatable tasks are not given a timeout
+    pub fn for_task(repeatable: bool) -> Self {
+        TaskPolicy {
+            timeout: if repeatable {
+                None
+            } else {
+                TaskPolicy::default().timeout
+            },
+            ..TaskPolicy::default()
+        }
+    }
 }
 
 impl Clone for Task {
@@ -20,6 +106,10 @@ impl Clone for Task {
             func: Arc::clone(&self.func),
             pre_condition: Arc::clone(&self.pre_condition),
             repeatable: self.repeatable,
+            terminal: self.terminal,
+            fork: self.fork,
+            join: self.join,
+            policy: self.policy,
         }
     }
 }
@@ -38,6 +128,10 @@ impl Task {
             func: Arc::new(func),
             pre_condition: Arc::new(pre_condition),
             repeatable,
+            terminal: false,
+            fork: false,
+            join: false,
+            policy: TaskPolicy::for_task(repeatable),
         }
     }
 }
@@ -60,6 +154,259 @@ impl Task {
             func: Arc::new(move |ctx| Box::pin(func(ctx))),
             pre_condition: Arc::new(move |ctx| Box::pin(pre_condition(ctx))),
             repeatable,
+            terminal: false,
+            fork: false,
+            join: false,
+            policy: TaskPolicy::for_task(repeatable),
         }
     }
+
+    pub fn with_policy(mut self, policy: TaskPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_terminal(mut self, terminal: bool) -> Self {
+        self.terminal = terminal;
+        self
+    }
+
+    pub fn with_fork(mut self, fork: bool) -> Self {
+        self.fork = fork;
+        self
+    }
+
+    pub fn with_join(mut self, join: bool) -> Self {
+        self.join = join;
+        self
+    }
+}
+
+pub type TaskFn =
+    Arc<dyn Fn(Context) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send + Sync>;
+
+pub type Condition =
+    Arc<dyn Fn(Context) -> Pin<Box<dyn Future<Output = Result<bool>> + Send>> + Send + Sync>;
+
+/// Named condition attached to an edge of the task graph
+#[derive(Clone)]
+pub struct Guard {
+    pub name: String,
+    pub check: Condition,
+}
+
+impl Guard {
+    pub fn new_async<P, Pout>(name: &str, check: P) -> Self
+    where
+        P: Fn(Context) -> Pout + Send + Sync + 'static,
+        Pout: Future<Output = Result<bool>> + Send + 'static,
+    {
+        Guard {
+            name: name.to_string(),
+            check: Arc::new(move |ctx| Box::pin(check(ctx))),
+        }
+    }
+}
+
+/// Per-instance parameters of a task, e.g. the URL to navigate to or the selector to click
+#[derive(Clone, Debug, Default)]
+pub struct TaskParams(HashMap<String, String>);
+
+impl TaskParams {
+    pub fn new(params: HashMap<String, String>) -> Self {
+        TaskParams(params)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(|v| v.as_str())
+    }
+
+    pub fn require(&self, key: &str) -> Result<&str> {
+        self.get(key)
+            .ok_or_else(|| anyhow!("Missing required task parameter `{key}`"))
+    }
+}
+
+type TaskFactory = Arc<dyn Fn(&str, &TaskParams, bool) -> Result<Task> + Send + Sync>;
+
+/// Maps stable task kind identifiers (e.g. `browser.open`) to task constructors, and guard
+/// identifiers (e.g. `microsoft.mfa_prompt`) to edge guards
+#[derive(Clone, Default)]
+pub struct TaskRegistry {
+    factories: HashMap<String, TaskFactory>,
+    guards: HashMap<String, Guard>,
+}
+
+impl TaskRegistry {
+    /// Registers a task kind which takes no parameters
+    pub fn register<F, Fut, P, Pout>(&mut self, kind: &str, func: F, pre_condition: P)
+    where
+        F: Fn(Context) -> Fut + Clone + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+        P: Fn(Context) -> Pout + Clone + Send + Sync + 'static,
+        Pout: Future<Output = Result<bool>> + Send + 'static,
+    {
+        self.register_with(kind, move |name, _, repeatable| {
+            Ok(Task::new_async(
+                name,
+                func.clone(),
+                pre_condition.clone(),
+                repeatable,
+            ))
+        });
+    }
+
+    /// Registers a task kind whose instances are built from their parameters.
+    ///
+    /// Panics if the kind is already registered.
+    pub fn register_with<B>(&mut self, kind: &str, builder: B)
+    where
+        B: Fn(&str, &TaskParams, bool) -> Result<Task> + Send + Sync + 'static,
+    {
+        let previous = self.factories.insert(kind.to_string(), Arc::new(builder));
+        assert!(previous.is_none(), "Task kind `{kind}` is registered twice");
+    }
+
+    pub fn instantiate(
+        &self,
+        kind: &str,
+        name: &str,
+        params: &TaskParams,
+        repeatable: bool,
+    ) -> Result<Task> {
+        let factory = self
+            .factories
+            .get(kind)
+            .ok_or_else(|| anyhow!("Unknown task kind `{kind}` for node `{name}`"))?;
+        factory(name, params, repeatable)
+            .map_err(|e| anyhow!("Failed to create task `{name}` of kind `{kind}`: {e}"))
+    }
+
+    /// Registers a guard edges can refer to by `name`.
+    ///
+    /// Panics if the name is already registered.
+    pub fn register_guard<P, Pout>(&mut self, name: &str, check: P)
+    where
+        P: Fn(Context) -> Pout + Send + Sync + 'static,
+        Pout: Future<Output = Result<bool>> + Send + 'static,
+    {
+        let previous = self
+            .guards
+            .insert(name.to_string(), Guard::new_async(name, check));
+        assert!(previous.is_none(), "Guard `{name}` is registered twice");
+    }
+
+    pub fn guard(&self, name: &str) -> Result<Guard> {
+        self.guards
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Unknown guard `{name}`"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> TaskRegistry {
+        let mut registry = TaskRegistry::default();
+        registry.register("test.noop", |_| async { Ok(()) }, |_| async { Ok(true) });
+        registry.register_with("test.open", |name, params, repeatable| {
+            params.require("url")?;
+            Ok(Task::new_async(
+                name,
+                |_| async { Ok(()) },
+                |_| async { Ok(true) },
+                repeatable,
+            ))
+        });
+        registry.register_guard("test.guard", |_| async { Ok(true) });
+        registry
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> TaskParams {
+        TaskParams::new(
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn instantiates_registered_kinds() {
+        let registry = registry();
+        let task = registry
+            .instantiate("test.noop", "Noop", &params(&[]), true)
+            .unwrap();
+        assert_eq!(task.name, "Noop");
+        assert!(task.repeatable);
+        assert_eq!(task.policy.timeout, None);
+
+        let task = registry
+            .instantiate(
+                "test.open",
+                "Open",
+                &params(&[("url", "https://wa.test")]),
+                false,
+            )
+            .unwrap();
+        assert_eq!(task.policy, TaskPolicy::default());
+        assert_eq!(registry.guard("test.guard").unwrap().name, "test.guard");
+    }
+
+    #[test]
+    fn reports_unknown_kinds_and_failed_builders() {
+        let registry = registry();
+        let error = |kind: &str| {
+            registry
+                .instantiate(kind, "Node", &params(&[]), false)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            error("test.missing"),
+            "Unknown task kind `test.missing` for node `Node`"
+        );
+        assert_eq!(
+            error("test.open"),
+            "Failed to create task `Node` of kind `test.open`: Missing required task parameter `url`"
+        );
+        assert_eq!(
+            registry.guard("test.missing").err().unwrap().to_string(),
+            "Unknown guard `test.missing`"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Task kind `test.noop` is registered twice")]
+    fn rejects_kinds_registered_twice() {
+        let mut registry = registry();
+        registry.register("test.noop", |_| async { Ok(()) }, |_| async { Ok(true) });
+    }
+
+    #[test]
+    #[should_panic(expected = "Guard `test.guard` is registered twice")]
+    fn rejects_guards_registered_twice() {
+        let mut registry = registry();
+        registry.register_guard("test.guard", |_| async { Ok(false) });
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_its_maximum() {
+        let backoff = Backoff::Exponential {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(5),
+        };
+        let delays: Vec<u64> = (1..=4)
+            .map(|retry| backoff.delay(retry).as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 5]);
+        let jittered = Backoff::Jittered {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(5),
+        };
+        assert!(jittered.delay(10) <= Duration::from_secs(5));
+    }
 }
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use petgraph::algo::tarjan_scc;
+use petgraph::prelude::*;
+use petgraph::visit::{Dfs, EdgeFiltered};
+
+use crate::task::Task;
+use crate::TaskEdge;
+
+/// Problems found in a task graph before it is executed
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphIssue {
+    Unreachable { name: String },
+    UnmarkedTerminal { name: String },
+    DuplicateName { name: String, count: usize },
+    InconsistentEdge { source: String, target: String },
+    NonRepeatableCycle { names: Vec<String> },
+}
+
+impl fmt::Display for GraphIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphIssue::Unreachable { name } => {
+                write!(f, "Node `{name}` is unreachable from the start node")
+            }
+            GraphIssue::UnmarkedTerminal { name } => write!(
+                f,
+                "Node `{name}` has no outgoing edges but is not marked as terminal"
+            ),
+            GraphIssue::DuplicateName { name, count } => {
+                write!(f, "Task name `{name}` is used by {count} nodes")
+            }
+            GraphIssue::InconsistentEdge { source, target } => write!(
+                f,
+                "Edge `{source}` -> `{target}` has no matching reverse edge of the opposite direction"
+            ),
+            GraphIssue::NonRepeatableCycle { names } => write!(
+                f,
+                "Cycle through `{}` does not pass through a repeatable node",
+                names.join("`, `")
+            ),
+        }
+    }
+}
+
+/// Checks the graph for mistakes which would otherwise only show up as timeouts at runtime
+pub fn validate(graph: &DiGraph<Task, TaskEdge>, start: NodeIndex) -> Vec<GraphIssue> {
+    let mut issues = Vec::new();
+
+    let mut reachable = Vec::new();
+    let outgoing_graph = EdgeFiltered::from_fn(graph, |edge| edge.weight().is_outgoing());
+    let mut dfs = Dfs::new(&outgoing_graph, start);
+    while let Some(node) = dfs.next(&outgoing_graph) {
+        reachable.push(node);
+    }
+    for node in graph.node_indices() {
+        if !reachable.contains(&node) {
+            issues.push(GraphIssue::Unreachable {
+                name: graph[node].name.clone(),
+            });
+        }
+    }
+
+    for node in graph.node_indices() {
+        let has_outgoing = graph.edges(node).any(|edge| edge.weight().is_outgoing());
+        if !has_outgoing && !graph[node].terminal {
+            issues.push(GraphIssue::UnmarkedTerminal {
+                name: graph[node].name.clone(),
+            });
+        }
+    }
+
+    let mut names: HashMap<&str, usize> = HashMap::new();
+    for node in graph.node_indices() {
+        *names.entry(graph[node].name.as_str()).or_default() += 1;
+    }
+    let mut duplicates: Vec<_> = names.into_iter().filter(|(_, count)| *count > 1).collect();
+    duplicates.sort();
+    for (name, count) in duplicates {
+        issues.push(GraphIssue::DuplicateName {
+            name: name.to_string(),
+            count,
+        });
+    }
+
+    // Two nodes may lead to each other, in which case both directions are outgoing edges
+    for edge in graph.edge_references() {
+        let expected = edge.weight().reversed().kind;
+        let consistent = graph
+            .edges_connecting(edge.target(), edge.source())
+            .any(|reverse| {
+                reverse.weight().kind == expected
+                    || (edge.weight().is_outgoing() && reverse.weight().is_outgoing())
+            });
+        if !consistent {
+            issues.push(GraphIssue::InconsistentEdge {
+                source: graph[edge.source()].name.clone(),
+                target: graph[edge.target()].name.clone(),
+            });
+        }
+    }
+
+    // Cycles are only allowed if a repeatable node is part of them, so look for cycles among the
+    // non-repeatable nodes alone
+    let non_repeatable = graph.filter_map(
+        |_, task| (!task.repeatable).then(|| task.name.clone()),
+        |_, edge| edge.is_outgoing().then_some(()),
+    );
+    for component in tarjan_scc(&non_repeatable) {
+        let is_cycle = component.len() > 1
+            || non_repeatable
+                .find_edge(component[0], component[0])
+                .is_some();
+        if is_cycle {
+            let mut names: Vec<String> = component
+                .iter()
+                .map(|&node| non_repeatable[node].clone())
+                .collect();
+            names.sort();
+            issues.push(GraphIssue::NonRepeatableCycle { names });
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ensure_bidirectional_edges;
+
+    fn task(name: &str, repeatable: bool, terminal: bool) -> Task {
+        Task::new_async(
+            name,
+            |_| async { Ok(()) },
+            |_| async { Ok(true) },
+            repeatable,
+        )
+        .with_terminal(terminal)
+    }
+
+    /// Graph of the given nodes with outgoing edges between them, the first node is the start
+    fn graph(nodes: Vec<Task>, edges: &[(usize, usize)]) -> (DiGraph<Task, TaskEdge>, NodeIndex) {
+        let mut graph = DiGraph::new();
+        let indices: Vec<NodeIndex> = nodes.into_iter().map(|node| graph.add_node(node)).collect();
+        for &(source, target) in edges {
+            graph.add_edge(
+                indices[source],
+                indices[target],
+                TaskEdge::outgoing(0, None),
+            );
+        }
+        ensure_bidirectional_edges(&mut graph);
+        (graph, indices[0])
+    }
+
+    #[test]
+    fn accepts_a_valid_graph() {
+        let (graph, start) = graph(
+            vec![task("Start", false, false), task("End", false, true)],
+            &[(0, 1)],
+        );
+        assert_eq!(validate(&graph, start), vec![]);
+    }
+
+    #[test]
+    fn finds_unreachable_nodes() {
+        let (graph, start) = graph(
+            vec![
+                task("Start", false, false),
+                task("End", false, true),
+                task("Orphan", false, false),
+            ],
+            &[(0, 1), (2, 1)],
+        );
+        assert_eq!(
+            validate(&graph, start),
+            vec![GraphIssue::Unreachable {
+                name: "Orphan".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn finds_unmarked_terminals() {
+        let (graph, start) = graph(
+            vec![task("Start", false, false), task("End", false, false)],
+            &[(0, 1)],
+        );
+        assert_eq!(
+            validate(&graph, start),
+            vec![GraphIssue::UnmarkedTerminal {
+                name: "End".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn finds_duplicate_names() {
+        let (graph, start) = graph(
+            vec![
+                task("Start", false, false),
+                task("End", false, true),
+                task("End", false, true),
+            ],
+            &[(0, 1), (0, 2)],
+        );
+        assert_eq!(
+            validate(&graph, start),
+            vec![GraphIssue::DuplicateName {
+                name: "End".to_string(),
+                count: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn finds_edges_without_a_reverse() {
+        let (mut graph, start) = graph(
+            vec![task("Start", false, false), task("End", false, true)],
+            &[(0, 1)],
+        );
+        let (source, target) = (start, NodeIndex::new(1));
+        graph.remove_edge(graph.find_edge(target, source).unwrap());
+        assert_eq!(
+            validate(&graph, start),
+            vec![GraphIssue::InconsistentEdge {
+                source: "Start".to_string(),
+                target: "End".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn accepts_nodes_leading_to_each_other() {
+        let (graph, start) = graph(
+            vec![
+                task("Start", false, false),
+                task("Wait", true, false),
+                task("End", false, true),
+            ],
+            &[(0, 1), (1, 0), (1, 2)],
+        );
+        assert_eq!(validate(&graph, start), vec![]);
+    }
+
+    #[test]
+    fn finds_cycles_without_a_repeatable_node() {
+        let (graph, start) = graph(
+            vec![
+                task("Start", false, false),
+                task("Retry", false, false),
+                task("End", false, true),
+            ],
+            &[(0, 1), (1, 0), (1, 2)],
+        );
+        assert_eq!(
+            validate(&graph, start),
+            vec![GraphIssue::NonRepeatableCycle {
+                names: vec!["Retry".to_string(), "Start".to_string()]
+            }]
+        );
+    }
+}
@@ -1,12 +1,22 @@
-use crate::{run_with_timeout, Context};
+use crate::browser::{navigate_to_webadvisor_precondition, webadvisor_base_url};
+use crate::clock::ServerClock;
+use crate::colleague::{ColleagueClient, RegistrationOutcome, RegistrationResponse};
+use crate::context::Context;
+use crate::driver::{Page, ResponseLog};
+use crate::rate::RateConfig;
+use crate::run_with_timeout;
+use crate::schedule::{warm, Schedule};
+use crate::task::{Task, TaskRegistry};
 use anyhow::anyhow;
 use anyhow::Result;
 use dotenv::var;
-use headless_chrome::Tab;
+use serde::Deserialize;
 use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::time::sleep;
+use tokio::time::{sleep, Instant};
 
 /// Navigates webadvisor
 
@@ -22,8 +32,10 @@ pub struct Date {
     pub semester: Semester,
     pub year: u32,
 }
-impl Date {
-    pub fn from_str(input: &str) -> Result<Self> {
+impl FromStr for Date {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
         let parts: Vec<&str> = input.split_whitespace().collect();
         if parts.len() != 2 {
             return Err(anyhow!("Invalid format"));
@@ -42,6 +54,30 @@ impl Date {
     }
 }
 
+impl Date {
+    /// Colleague's code for the term, e.g. `F25` for Fall 2025
+    pub fn code(&self) -> String {
+        let season = match self.semester {
+            Semester::Winter => 'W',
+            Semester::Summer => 'S',
+            Semester::Fall => 'F',
+        };
+        format!("{season}{:02}", self.year % 100)
+    }
+}
+
+/// Terms of `year` in the order the schedule page steps through them
+pub fn terms_of_year(year: u32) -> Vec<String> {
+    ["Winter", "Summer", "Fall"]
+        .iter()
+        .map(|season| format!("{season} {year}"))
+        .collect()
+}
+
+/// Term the schedule page was navigated to, stored on the context
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ActiveTerm(pub Date);
+
 impl PartialOrd for Date {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         match self.year.cmp(&other.year) {
@@ -51,38 +87,13 @@ impl PartialOrd for Date {
         }
     }
 }
-fn extract_number(input: &str) -> Result<i64> {
-    let mut number_str = String::new();
-    for c in input.chars().rev() {
-        if c.is_ascii_digit() {
-            number_str.push(c);
-        } else if !number_str.is_empty() {
-            break;
-        }
-    }
-
-    if number_str.is_empty() {
-        Err(anyhow::anyhow!("No number found in the input string"))
-    } else {
-        let number: i64 = number_str.chars().rev().collect::<String>().parse()?;
-        Ok(number)
-    }
-}
 
 pub async fn wa_navigate_semester_precondition(context: Context) -> Result<bool> {
-    let guard = context
-        .inner
-        .read()
-        .map_err(|_| anyhow!("Poison error"))?;
-    if guard.is_none() {
-        return Err(anyhow!("Expected browser + tab, found `None`."));
-    }
-    let tab = guard.as_ref().unwrap().tab.clone();
-    drop(guard);
-    tab.wait_until_navigated()?;
-    tab.find_element("#schedule-next-term")?;
-    tab.find_element("#schedule-prev-term")?;
-    tab.find_element("#schedule-activeterm-text")?;
+    let page = context.page()?;
+    page.wait_until_navigated()?;
+    page.find_element("#schedule-next-term")?;
+    page.find_element("#schedule-prev-term")?;
+    page.find_element("#schedule-activeterm-text")?;
     Ok(true)
 }
 
@@ -105,35 +116,26 @@ where
 }
 
 pub async fn wa_navigate_semester_function(context: Context) -> Result<()> {
-    let tab = {
-        let guard = context
-            .inner
-            .read()
-            .map_err(|_| anyhow!("Poison error"))?;
-        if guard.is_none() {
-            return Err(anyhow!("Expected browser + tab, found `None`."));
-        }
-        guard.as_ref().unwrap().tab.clone()
-    };
+    let page = context.page()?;
 
     let target_date = Date::from_str(var("TARGET_SEMESTER")?.as_str())?;
+    let navigated_context = context.clone();
     run_with_timeout(
         async move {
             loop {
-                tab.wait_until_navigated()?;
+                page.wait_until_navigated()?;
 
                 let result = retry_interaction(|| {
-                    let next = tab.find_element("#schedule-next-term")?;
-                    let prev = tab.find_element("#schedule-prev-term")?;
-                    let text = tab.find_element("#schedule-activeterm-text")?;
-                    let text = text.get_inner_text()?;
+                    page.find_element("#schedule-next-term")?;
+                    page.find_element("#schedule-prev-term")?;
+                    let text = page.inner_text("#schedule-activeterm-text")?;
                     let date_current = Date::from_str(&text)?;
                     match date_current.partial_cmp(&target_date) {
                         Some(ord) => {
                             match ord {
-                                Ordering::Less => prev.click()?,
+                                Ordering::Less => page.click("#schedule-prev-term")?,
                                 Ordering::Equal => return Ok(true),
-                                Ordering::Greater => next.click()?,
+                                Ordering::Greater => page.click("#schedule-next-term")?,
                             };
                         }
                         _ => return Err(anyhow!("Failed to compare dates")),
@@ -142,6 +144,7 @@ pub async fn wa_navigate_semester_function(context: Context) -> Result<()> {
                 })
                 .await;
                 if let Ok(true) = result {
+                    navigated_context.insert(ActiveTerm(target_date))?;
                     return Ok(());
                 }
                 if let Err(e) = result {
@@ -157,85 +160,273 @@ pub async fn wa_navigate_semester_function(context: Context) -> Result<()> {
 }
 
 pub async fn wa_register_precondition(context: Context) -> Result<bool> {
-    let tab = {
-        let guard = context
-            .inner
-            .read()
-            .map_err(|_| anyhow!("Poison error"))?;
-        if guard.is_none() {
-            return Err(anyhow!("Expected browser + tab, found `None`."));
-        }
-        guard.as_ref().unwrap().tab.clone()
-    };
-    tab.find_element("#register-button")?;
+    let page = context.page()?;
+    page.find_element("#register-button")?;
     Ok(true)
 }
 
-pub fn button_pressing(tab: &Arc<Tab>) -> Result<f32> {
-    unsafe {
-        if let Ok(button) = tab.find_element("#register-button") {
-            button.click()?;
+/// Outcomes in `REGISTER_UNTIL` (comma separated, defaults to `registered`) a section has to
+/// reach for the registration to be done
+pub fn desired_outcomes() -> Result<Vec<RegistrationOutcome>> {
+    match var("REGISTER_UNTIL") {
+        Ok(outcomes) => outcomes.split(',').map(str::parse).collect(),
+        Err(_) => Ok(vec![RegistrationOutcome::Registered]),
+    }
+}
+
+/// Splits a comma separated list of sections
+fn parse_sections(sections: &str) -> Vec<String> {
+    sections
+        .split(',')
+        .map(|section| section.trim().to_string())
+        .filter(|section| !section.is_empty())
+        .collect()
+}
+
+/// The node's `sections`, else the sections in `REGISTER_SECTIONS` (comma separated), else all
+/// of `planned`
+pub fn target_sections(planned: &[String], sections: Option<&[String]>) -> Vec<String> {
+    if let Some(sections) = sections {
+        return sections.to_vec();
+    }
+    match var("REGISTER_SECTIONS") {
+        Ok(sections) => parse_sections(&sections),
+        Err(_) => planned.to_vec(),
+    }
+}
+
+/// Last known outcome of every section, stored on the context by the register tasks
+#[derive(Clone, Debug, Default)]
+pub struct SectionOutcomes(pub HashMap<String, RegistrationOutcome>);
+
+fn record_outcomes(
+    found: Vec<(String, RegistrationOutcome)>,
+    outcomes: &mut HashMap<String, RegistrationOutcome>,
+) {
+    for (section, outcome) in found {
+        if outcomes.insert(section.clone(), outcome) != Some(outcome) {
+            println!("{section}: {outcome}");
+        }
+    }
+}
+
+/// Targets whose last outcome isn't one of the `desired` ones, failing when retrying can't
+/// change any of them
+fn pending_sections(
+    targets: &[String],
+    outcomes: &HashMap<String, RegistrationOutcome>,
+    desired: &[RegistrationOutcome],
+) -> Result<Vec<String>> {
+    let pending: Vec<_> = targets
+        .iter()
+        .filter(|section| {
+            !outcomes
+                .get(*section)
+                .is_some_and(|outcome| desired.contains(outcome))
+        })
+        .cloned()
+        .collect();
+    let stuck: Vec<_> = pending
+        .iter()
+        .filter_map(|section| {
+            let outcome = outcomes
+                .get(section)
+                .filter(|outcome| outcome.is_permanent())?;
+            Some(format!("{section} ({outcome})"))
+        })
+        .collect();
+    if !pending.is_empty() && stuck.len() == pending.len() {
+        return Err(anyhow!("Can't register {}", stuck.join(", ")));
+    }
+    Ok(pending)
+}
+
+/// Notification banners shown after registering
+const BANNER_SELECTOR: &str = "#notificationHost .notification-message";
+/// Planned course rows, each with its section in `data-section-id` and a status badge
+const COURSE_SELECTOR: &str = ".schedule-course[data-section-id]";
+const BADGE_SELECTOR: &str = ".schedule-course-status";
+
+/// Notification banners and course status badges on the schedule page
+#[derive(Debug, Default, Deserialize)]
+struct ScheduleStatus {
+    banners: Vec<String>,
+    /// Badge text by section
+    badges: HashMap<String, String>,
+}
+
+impl ScheduleStatus {
+    fn read(page: &Arc<dyn Page>) -> Result<Self> {
+        let script = format!(
+            r#"
+        JSON.stringify({{
+            banners: [...document.querySelectorAll("{BANNER_SELECTOR}")]
+                .map(banner => banner.innerText.trim()),
+            badges: Object.fromEntries([...document.querySelectorAll("{COURSE_SELECTOR}")]
+                .map(course => [
+                    course.dataset.sectionId,
+                    course.querySelector("{BADGE_SELECTOR}")?.innerText.trim() ?? "",
+                ])),
+        }})
+    "#
+        );
+        match page.evaluate(&script)? {
+            Some(serde_json::Value::String(status)) => Ok(serde_json::from_str(&status)?),
+            _ => Ok(ScheduleStatus::default()),
         }
     }
-    let button = tab.wait_for_element("#register-button")?;
+
+    /// Outcomes of the banners naming a section, followed by the outcomes the badges show
+    fn outcomes(&self) -> Vec<(String, RegistrationOutcome)> {
+        let banners = self.banners.iter().flat_map(|banner| {
+            self.badges
+                .keys()
+                .filter(|section| banner.contains(section.as_str()))
+                .map(|section| (section.clone(), RegistrationOutcome::from_message(banner)))
+        });
+        let badges = self
+            .badges
+            .iter()
+            .map(|(section, badge)| (section.clone(), RegistrationOutcome::from_message(badge)));
+        banners
+            .chain(badges)
+            .filter(|(_, outcome)| *outcome != RegistrationOutcome::Unknown)
+            .collect()
+    }
+}
+
+pub fn button_pressing(page: &Arc<dyn Page>) -> Result<f32> {
+    if page.find_element("#register-button").is_ok() {
+        page.click("#register-button")?;
+    }
+    page.wait_for_element("#register-button")?;
     let script = format!(
         r#"
         document.querySelector("{}").removeAttribute("disabled");
     "#,
         "#register-button"
     );
-    tab.evaluate(script.as_str(), true)?;
-    button.click()?;
+    page.evaluate(script.as_str())?;
+    page.click("#register-button")?;
     Ok(1.0)
 }
 
-pub async fn wa_register_function(context: Context) -> Result<()> {
-    let tab = {
-        let guard = context
-            .inner
-            .read()
-            .map_err(|_| anyhow!("Poison error"))?;
-        if guard.is_none() {
-            return Err(anyhow!("Expected browser + tab, found `None`."));
+/// Waits for the registration window if an opening time is scheduled
+async fn wait_for_window(context: &Context) -> Result<()> {
+    if let Some(schedule) = context.get::<Schedule>()? {
+        let clock = context.get::<ServerClock>()?.unwrap_or_default();
+        schedule.wait(&context.page()?, &clock).await?;
+    }
+    Ok(())
+}
+
+/// Continue button of the dialog Colleague shows when the session is about to time out
+const TIMEOUT_CONTINUE_SELECTOR: &str = "#session-timeout-continue";
+
+/// Whether the keep-alive task found the session signed out, stored on the context
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SessionExpired(pub bool);
+
+/// Clicks the timeout dialog's continue button if the dialog is showing
+fn dismiss_timeout_dialog(page: &Arc<dyn Page>) -> Result<bool> {
+    let dismissed = page.evaluate(&format!(
+        r#"(() => {{
+            const button = document.querySelector("{TIMEOUT_CONTINUE_SELECTOR}");
+            if (!button || button.offsetParent === null) return false;
+            button.click();
+            return true;
+        }})()"#
+    ))?;
+    Ok(dismissed
+        .and_then(|dismissed| dismissed.as_bool())
+        .unwrap_or(false))
+}
+
+/// Keeps the session alive until `lead` before the registration window opens, dismissing the
+/// timeout dialog and pinging the session every `SESSION_WARM_INTERVAL`. Without a schedule the
+/// session is only checked once. Ends early with [`SessionExpired`] set once the session is
+/// signed out, so the graph can sign in again.
+pub async fn wa_keep_alive_function(context: Context, lead: Duration) -> Result<()> {
+    const DIALOG_POLL: Duration = Duration::from_secs(5);
+    let page = context.page()?;
+    context.insert(SessionExpired(false))?;
+    let schedule = context.get::<Schedule>()?;
+    let clock = context.get::<ServerClock>()?.unwrap_or_default();
+    let mut last_ping: Option<Instant> = None;
+    loop {
+        if dismiss_timeout_dialog(&page)? {
+            println!("Dismissed the session timeout dialog");
         }
-        guard.as_ref().unwrap().tab.clone()
-    };
+        let interval = schedule.map_or(Duration::ZERO, |schedule| schedule.warm_interval);
+        if last_ping.is_none_or(|last| last.elapsed() >= interval) {
+            match warm(&page) {
+                Ok(true) => {}
+                Ok(false) => {
+                    println!("The session was signed out, signing in again");
+                    context.insert(SessionExpired(true))?;
+                    return Ok(());
+                }
+                Err(e) => eprintln!("Failed to ping the session: {e}"),
+            }
+            last_ping = Some(Instant::now());
+        }
+        let Some(schedule) = schedule else {
+            return Ok(());
+        };
+        let remaining = schedule.remaining(clock.now());
+        if remaining <= lead {
+            return Ok(());
+        }
+        sleep((remaining - lead).min(DIALOG_POLL)).await;
+    }
+}
 
-    let mut lower_bound = 0.1;
-    let mut upper_bound = 10.0;
-    let mut wait_time: f32 = (lower_bound + upper_bound) / 2.0;
+pub async fn wa_session_expired(context: Context) -> Result<bool> {
+    Ok(context
+        .get::<SessionExpired>()?
+        .is_some_and(|SessionExpired(expired)| expired))
+}
 
-    let mut success_count = 0;
-    let mut failure_count = 0;
-    let mut consecutive_successes = 0;
-    let mut total_fails = 0;
+pub async fn wa_register_function(
+    context: Context,
+    rate: RateConfig,
+    sections: Option<Vec<String>>,
+) -> Result<()> {
+    let page = context.page()?;
+    if let Some(ActiveTerm(term)) = context.get::<ActiveTerm>()? {
+        println!("Registering for {:?} {}", term.semester, term.year);
+    }
+    wait_for_window(&context).await?;
+
+    let desired = desired_outcomes()?;
+    let responses = ResponseLog::default();
+    if let Err(e) = page.intercept_responses("*/RegisterSections*", responses.clone()) {
+        eprintln!("Not watching registration responses: {e}");
+    }
+    let mut outcomes = HashMap::new();
+    let mut planned: Vec<String> = Vec::new();
+
+    let mut rate = rate.policy();
+    let started = Instant::now();
 
-    const SUCCESS_THRESHOLD: usize = 5;
-    const FAILURE_THRESHOLD: usize = 3;
+    // The rate policy decides the delay, this only gives up once pressing keeps failing
+    let mut total_fails = 0;
     const MAX_TOTAL_FAILS: usize = 10;
+    // The schedule may take a few reads to show up, but without targets the loop can't end
+    const MAX_READS_WITHOUT_TARGETS: usize = 10;
+    let mut reads_without_targets = 0;
 
     loop {
-        let result = button_pressing(&tab);
+        let result = button_pressing(&page);
+        rate.record(started.elapsed(), result.is_ok());
         match result {
             Ok(_) => {
                 println!("Button pressed successfully. Recording success.");
-                success_count += 1;
-                consecutive_successes += 1;
-                failure_count = 0;
-
-                if consecutive_successes >= SUCCESS_THRESHOLD {
-                    // If we have enough consecutive successes, reduce the upper bound
-                    upper_bound = wait_time;
-                    wait_time = (lower_bound + upper_bound) / 2.0;
-                    consecutive_successes = 0; // Reset consecutive success count
-                    total_fails = 0;
-                }
+                total_fails = 0;
             }
             Err(e) => {
-                eprintln!("Error pressing button: {}. Increasing wait time.", e);
-                failure_count += 1;
+                eprintln!("Error pressing button: {}.", e);
                 total_fails += 1;
-                consecutive_successes = 0;
 
                 if total_fails >= MAX_TOTAL_FAILS {
                     return Err(anyhow!(
@@ -243,24 +434,257 @@ pub async fn wa_register_function(context: Context) -> Result<()> {
                         e
                     ));
                 }
+            }
+        }
+
+        for response in responses.take() {
+            match serde_json::from_str::<RegistrationResponse>(&response.body) {
+                Ok(response) => record_outcomes(response.outcomes(), &mut outcomes),
+                Err(e) => eprintln!(
+                    "Unreadable registration response (status {}): {e}",
+                    response.status
+                ),
+            }
+        }
+        match ScheduleStatus::read(&page) {
+            Ok(schedule) => {
+                planned.extend(schedule.badges.keys().cloned());
+                record_outcomes(schedule.outcomes(), &mut outcomes);
+            }
+            Err(e) => eprintln!("Failed to read the schedule: {e}"),
+        }
+        planned.extend(outcomes.keys().cloned());
+        planned.sort();
+        planned.dedup();
+        // Sibling branches share the store, so merge instead of replacing their outcomes
+        context
+            .update(|SectionOutcomes(all): &mut SectionOutcomes| all.extend(outcomes.clone()))?;
+        let targets = target_sections(&planned, sections.as_deref());
+        if targets.is_empty() {
+            reads_without_targets += 1;
+            if reads_without_targets >= MAX_READS_WITHOUT_TARGETS {
+                return Err(anyhow!(
+                    "No target sections found after {reads_without_targets} reads of the schedule"
+                ));
+            }
+        } else if pending_sections(&targets, &outcomes, &desired)?.is_empty() {
+            println!("Every target section reached a desired outcome");
+            return Ok(());
+        }
+
+        let wait_time = rate.next_delay(started.elapsed());
+        println!("Current wait time: {:?}", wait_time);
+        sleep(wait_time).await;
+    }
+}
+
+/// Registers the sections planned for the active term through the JSON endpoints, using the
+/// browser's session instead of clicking
+pub async fn wa_register_http_function(
+    context: Context,
+    rate: RateConfig,
+    sections: Option<Vec<String>>,
+) -> Result<()> {
+    let page = context.page()?;
+    let term = match context.get::<ActiveTerm>()? {
+        Some(ActiveTerm(term)) => term,
+        None => Date::from_str(var("TARGET_SEMESTER")?.as_str())?,
+    };
+    wait_for_window(&context).await?;
+    let client = ColleagueClient::from_page(&page, &webadvisor_base_url())?;
+    let plan = client.load_plan().await?;
+    let targets = target_sections(&plan.sections(&term.code()), sections.as_deref());
+    if targets.is_empty() {
+        return Err(anyhow!("No sections planned for {}", term.code()));
+    }
+    println!(
+        "Registering for {} over HTTP: {}",
+        term.code(),
+        targets.join(", ")
+    );
+    let mut pending = targets.clone();
 
-                if failure_count >= FAILURE_THRESHOLD {
-                    // If we have enough consecutive failures, increase the lower bound
-                    lower_bound = wait_time;
-                    wait_time = (lower_bound + upper_bound) / 2.0;
-                    failure_count = 0; // Reset failure count
+    let desired = desired_outcomes()?;
+    let mut outcomes = HashMap::new();
+    let mut rate = rate.policy();
+    let started = Instant::now();
+    const MAX_TOTAL_FAILS: usize = 10;
+    let mut total_fails = 0;
+    while !pending.is_empty() {
+        let result = client.register(&plan.person_id, &pending).await;
+        rate.record(started.elapsed(), result.is_ok());
+        match result {
+            Ok(response) => {
+                record_outcomes(response.outcomes(), &mut outcomes);
+                context.update(|SectionOutcomes(all): &mut SectionOutcomes| {
+                    all.extend(outcomes.clone())
+                })?;
+                pending = pending_sections(&targets, &outcomes, &desired)?;
+            }
+            Err(e) => {
+                eprintln!("Registration request failed: {e}");
+                total_fails += 1;
+                if total_fails >= MAX_TOTAL_FAILS {
+                    return Err(anyhow!("Failed to register due to too many errors: {e}"));
                 }
             }
         }
+        if !pending.is_empty() {
+            sleep(rate.next_delay(started.elapsed())).await;
+        }
+    }
+    Ok(())
+}
+
+pub fn register(registry: &mut TaskRegistry) {
+    registry.register(
+        "wa.navigate_semester",
+        wa_navigate_semester_function,
+        wa_navigate_semester_precondition,
+    );
+    registry.register_with("wa.register", |name, params, repeatable| {
+        let rate = RateConfig::from_params(params)?;
+        let sections = params.get("sections").map(parse_sections);
+        Ok(Task::new_async(
+            name,
+            move |ctx| wa_register_function(ctx, rate, sections.clone()),
+            wa_register_precondition,
+            repeatable,
+        ))
+    });
+    registry.register_with("wa.register_http", |name, params, repeatable| {
+        let rate = RateConfig::from_params(params)?;
+        let sections = params.get("sections").map(parse_sections);
+        Ok(Task::new_async(
+            name,
+            move |ctx| wa_register_http_function(ctx, rate, sections.clone()),
+            wa_register_precondition,
+            repeatable,
+        ))
+    });
+    registry.register_with("wa.keep_alive", |name, params, repeatable| {
+        let lead = match params.get("lead") {
+            Some(lead) => Duration::try_from_secs_f64(lead.parse()?)?,
+            None => Duration::from_secs(30),
+        };
+        Ok(Task::new_async(
+            name,
+            move |ctx| wa_keep_alive_function(ctx, lead),
+            navigate_to_webadvisor_precondition,
+            repeatable,
+        ))
+    });
+    registry.register_guard("wa.signed_in", wa_navigate_semester_precondition);
+    registry.register_guard("wa.session_expired", wa_session_expired);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::mock::{mock_context, MockScript};
+
+    const PLAN_URL: &str = "https://wa.test/Student/Planning/DegreePlans";
+
+    fn signed_in() -> Context {
+        let terms = terms_of_year(2025);
+        mock_context(MockScript::webadvisor(PLAN_URL, &terms), PLAN_URL, true)
+    }
+
+    #[test]
+    fn dates_parse_into_term_codes() {
+        assert_eq!(Date::from_str("Fall 2025").unwrap().code(), "F25");
+        assert_eq!(Date::from_str("Winter 2031").unwrap().code(), "W31");
+        assert!(Date::from_str("Spring 2025").is_err());
+        assert!(Date::from_str("Fall").is_err());
+    }
 
-        // Log current statistics
-        println!(
-            "Success count: {}, Failure count: {}, Current wait time: {}",
-            success_count, failure_count, wait_time
+    #[tokio::test]
+    async fn navigates_to_the_target_semester() {
+        let context = signed_in();
+        assert!(wa_navigate_semester_precondition(context.clone())
+            .await
+            .unwrap());
+        wa_navigate_semester_function(context.clone())
+            .await
+            .unwrap();
+        let page = context.page().unwrap();
+        assert_eq!(
+            page.inner_text("#schedule-activeterm-text").unwrap(),
+            "Fall 2025"
         );
+        let ActiveTerm(term) = context.get::<ActiveTerm>().unwrap().unwrap();
+        assert_eq!(term.code(), "F25");
+    }
 
-        // Adjust wait time to stay within bounds
-        wait_time = wait_time.clamp(0.1, 10.0);
-        sleep(Duration::from_secs_f32(wait_time)).await;
+    #[tokio::test]
+    async fn register_stops_once_the_targets_are_registered() {
+        let context = signed_in();
+        assert!(wa_register_precondition(context.clone()).await.unwrap());
+        let rate = RateConfig::FixedJitter {
+            interval: Duration::ZERO,
+            jitter: Duration::ZERO,
+        };
+        let sections = vec!["CIS*2500*0101".to_string()];
+        wa_register_function(context.clone(), rate, Some(sections))
+            .await
+            .unwrap();
+        let SectionOutcomes(outcomes) = context.get::<SectionOutcomes>().unwrap().unwrap();
+        assert_eq!(
+            outcomes.get("CIS*2500*0101"),
+            Some(&RegistrationOutcome::Registered)
+        );
+    }
+
+    #[tokio::test]
+    async fn register_fails_without_target_sections() {
+        let context = signed_in();
+        let rate = RateConfig::FixedJitter {
+            interval: Duration::ZERO,
+            jitter: Duration::ZERO,
+        };
+        let error = wa_register_function(context, rate, Some(Vec::new()))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No target sections found after 10 reads of the schedule"
+        );
+    }
+
+    #[tokio::test]
+    async fn keep_alive_without_a_schedule_checks_once() {
+        let context = signed_in();
+        wa_keep_alive_function(context.clone(), Duration::from_secs(30))
+            .await
+            .unwrap();
+        assert!(!wa_session_expired(context).await.unwrap());
+    }
+
+    #[test]
+    fn node_sections_override_the_plan() {
+        let planned = vec!["CIS*2500*0101".to_string(), "MATH*1200*0102".to_string()];
+        let sections = parse_sections(" MATH*1200*0102 ,, ");
+        assert_eq!(
+            target_sections(&planned, Some(&sections)),
+            vec!["MATH*1200*0102".to_string()]
+        );
+    }
+
+    #[test]
+    fn pending_sections_fail_when_only_permanent_outcomes_remain() {
+        let targets = vec!["CIS*2500*0101".to_string(), "MATH*1200*0102".to_string()];
+        let desired = [RegistrationOutcome::Registered];
+        let mut outcomes = HashMap::new();
+        outcomes.insert("CIS*2500*0101".to_string(), RegistrationOutcome::Registered);
+        outcomes.insert("MATH*1200*0102".to_string(), RegistrationOutcome::Closed);
+        assert_eq!(
+            pending_sections(&targets, &outcomes, &desired).unwrap(),
+            vec!["MATH*1200*0102".to_string()]
+        );
+        outcomes.insert(
+            "MATH*1200*0102".to_string(),
+            RegistrationOutcome::TimeConflict,
+        );
+        assert!(pending_sections(&targets, &outcomes, &desired).is_err());
     }
 }